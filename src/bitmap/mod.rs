@@ -7,12 +7,16 @@
 //!
 //! [`ColorBuf`]: ../trait.ColorBuf.html
 
+use crate::vec::VecColorBuf;
 use crate::{Color, ColorBuf, ColorBufError};
+use std::convert::TryFrom;
+use std::fmt;
 use std::result::Result;
 
 /// Tells the [`BitmapColorBuf`] how the colors are arranged within the bitmap.
 ///
 /// [`BitmapColorBuf`]: struct.BitmapColorBuf.html
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ColorFormat {
     /// Representation where red is the low word, and alpha is the high word.
     RGBA,
@@ -20,13 +24,38 @@ pub enum ColorFormat {
     ARGB,
     /// Representation where red is the low word, and blue is the high word.
     RGB,
+    /// Representation where blue is the low word, and alpha is the high word.
+    BGRA,
+    /// Representation where blue is the low word, and red is the high word.
+    BGR,
+    /// Representation with a single luminance channel.
+    Grayscale,
+    /// Representation where luminance is the low word, and alpha is the high word.
+    GrayscaleAlpha,
 }
 
 /// Tells the [`BitmapColorBuf`] how many bits are used in the bitmap per channel.
 ///
+/// Channels wider than a byte are stored in little-endian byte order.
+///
 /// [`BitmapColorBuf`]: struct.BitmapColorBuf.html
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BitDepth {
+    /// Every channel is stored in a single byte.
     Eight,
+    /// Every channel is stored in two bytes.
+    Sixteen,
+    /// Every channel is stored as an IEEE 754 half precision float in two bytes.
+    ///
+    /// The channels are not limited to `[0, 1]`, which makes this suitable for HDR data.
+    SixteenFloat,
+    /// Every channel is stored as an `f32` in four bytes.
+    ///
+    /// Like [`SixteenFloat`], the channels are not limited to `[0, 1]`, but they are stored
+    /// exactly.
+    ///
+    /// [`SixteenFloat`]: #variant.SixteenFloat
+    ThirtyTwoFloat,
 }
 
 pub struct BitmapColorBuf {
@@ -37,106 +66,90 @@ pub struct BitmapColorBuf {
     rows: u64,
     pixels_per_row: u64,
     stride: u64,
+    premultiplied: bool,
 }
 
 impl ColorBuf for BitmapColorBuf {
     fn get_pixel(&self, x: u64, y: u64) -> Result<Color, ColorBufError> {
-        if x >= self.pixels_per_row || y >= self.rows {
-            return Err(ColorBufError::InvalidCoordinate);
-        }
-        let index = self.get_offset(x, y);
-        let r: f32;
-        let g: f32;
-        let b: f32;
-        let a: f32;
-
-        match self.format {
-            ColorFormat::RGBA => match self.depth {
-                BitDepth::Eight => {
-                    r = (self.data[index] as f32) / 255f32;
-                    g = (self.data[index + 1] as f32) / 255f32;
-                    b = (self.data[index + 2] as f32) / 255f32;
-                    a = (self.data[index + 3] as f32) / 255f32;
-                }
-            },
-            ColorFormat::ARGB => match self.depth {
-                BitDepth::Eight => {
-                    a = (self.data[index] as f32) / 255f32;
-                    r = (self.data[index + 1] as f32) / 255f32;
-                    g = (self.data[index + 2] as f32) / 255f32;
-                    b = (self.data[index + 3] as f32) / 255f32;
-                }
-            },
-            ColorFormat::RGB => match self.depth {
-                BitDepth::Eight => {
-                    r = (self.data[index] as f32) / 255f32;
-                    g = (self.data[index + 1] as f32) / 255f32;
-                    b = (self.data[index + 2] as f32) / 255f32;
-                    a = 1.0f32;
-                }
-            },
-        }
+        let index = self.get_offset(x, y)?;
+        Ok(self.read_pixel(index))
+    }
 
-        Ok(Color {
-            r: r,
-            g: g,
-            b: b,
-            a: a,
-        })
+    unsafe fn get_pixel_unchecked(&self, x: u64, y: u64) -> Color {
+        let index = self.get_unchecked_offset(x, y);
+        self.read_pixel(index)
     }
 
     fn set_pixel(&mut self, x: u64, y: u64, color: &Color) -> Result<(), ColorBufError> {
-        if x >= self.pixels_per_row || y >= self.rows {
-            return Err(ColorBufError::InvalidCoordinate);
+        let index = self.get_offset(x, y)?;
+        self.write_pixel(index, color);
+        Ok(())
+    }
+
+    unsafe fn set_pixel_unchecked(&mut self, x: u64, y: u64, color: &Color) {
+        let index = self.get_unchecked_offset(x, y);
+        self.write_pixel(index, color);
+    }
+
+    /// Decodes every pixel on row `y` at once.
+    ///
+    /// Tightly packed 8-bit RGBA rows take a fast path that skips the per-pixel format dispatch,
+    /// while every other layout falls back to decoding the pixels one by one.
+    fn get_row(&self, y: u64) -> Result<Vec<Color>, ColorBufError> {
+        let range = self.get_row_range(y)?;
+        let mut colors = match (&self.format, &self.depth) {
+            (ColorFormat::RGBA, BitDepth::Eight) => decode_rgba8_row(&self.data[range]),
+            _ => {
+                let bpp = get_bpp_factor(&self.format, &self.depth) as usize;
+                range
+                    .step_by(bpp)
+                    .map(|index| decode_pixel(&self.data, index, &self.format, &self.depth))
+                    .collect()
+            }
+        };
+        if self.premultiplied && has_alpha(&self.format) {
+            for color in colors.iter_mut() {
+                *color = color.unpremultiplied();
+            }
         }
-        let index = self.get_offset(x, y);
+        Ok(colors)
+    }
 
-        // The alpha channel gets ignored in the case of RGB backing, and becomes a dividand
-        // to the other color channels before application.
-        // XXX: Is this reasonable?
+    fn fill_rect(
+        &mut self,
+        x: u64,
+        y: u64,
+        width: u64,
+        height: u64,
+        color: &Color,
+    ) -> Result<(), ColorBufError> {
+        let end_x = x
+            .checked_add(width)
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        let end_y = y
+            .checked_add(height)
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        if end_x > self.pixels_per_row || end_y > self.rows {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        // Make sure the last row fits too, in case the buffer was created with too small a `data`.
+        let bpp = get_bpp_factor(&self.format, &self.depth) as usize;
+        let start = self.get_offset(x, end_y - 1)?;
+        if start + bpp * width as usize > self.data.len() {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
 
-        match self.format {
-            ColorFormat::RGBA => match self.depth {
-                BitDepth::Eight => {
-                    let r_byte = (color.r * 255f32) as u8;
-                    let g_byte = (color.g * 255f32) as u8;
-                    let b_byte = (color.b * 255f32) as u8;
-                    let a_byte = (color.a * 255f32) as u8;
-
-                    self.data[index] = r_byte;
-                    self.data[index + 1] = g_byte;
-                    self.data[index + 2] = b_byte;
-                    self.data[index + 3] = a_byte;
-                }
-            },
-            ColorFormat::ARGB => match self.depth {
-                BitDepth::Eight => {
-                    let r_byte = (color.r * 255f32) as u8;
-                    let g_byte = (color.g * 255f32) as u8;
-                    let b_byte = (color.b * 255f32) as u8;
-                    let a_byte = (color.a * 255f32) as u8;
-
-                    self.data[index] = a_byte;
-                    self.data[index + 1] = r_byte;
-                    self.data[index + 2] = g_byte;
-                    self.data[index + 3] = b_byte;
-                }
-            },
-            ColorFormat::RGB => match self.depth {
-                BitDepth::Eight => {
-                    let r = color.r / color.a;
-                    let g = color.g / color.a;
-                    let b = color.b / color.a;
-
-                    let r_byte = (r * 255f32) as u8;
-                    let g_byte = (g * 255f32) as u8;
-                    let b_byte = (b * 255f32) as u8;
-
-                    self.data[index] = r_byte;
-                    self.data[index + 1] = g_byte;
-                    self.data[index + 2] = b_byte;
-                }
-            },
+        // Encode the color once and copy the whole run of pixels into every row.
+        let mut pixel = vec![0; bpp];
+        let color = self.prepare_color(color);
+        encode_pixel(&mut pixel, 0, &self.format, &self.depth, &color);
+        let run = pixel.repeat(width as usize);
+        for y in y..end_y {
+            let start = self.get_unchecked_offset(x, y);
+            self.data[start..start + run.len()].copy_from_slice(&run);
         }
         Ok(())
     }
@@ -160,8 +173,13 @@ impl BitmapColorBuf {
     /// * `rows` - How many rows this bitmap image has?
     /// * `pixels_per_row` - The width of the image.
     /// * `stride` - How many bytes are between rows? For tightly packed bitmaps (i.e. no padding),
-    /// this is the same as `pixels_per_row`.
+    ///   this is the same as `pixels_per_row` times the amount of bytes per pixel.
     /// * `data` - The bitmap image.
+    ///
+    /// The layout is not validated against `data`, so a too small `data` or `stride` is a
+    /// programmer error. Use [`new_checked`] to have it validated.
+    ///
+    /// [`new_checked`]: #method.new_checked
     pub fn new(
         format: ColorFormat,
         depth: BitDepth,
@@ -177,35 +195,584 @@ impl BitmapColorBuf {
             rows,
             pixels_per_row,
             stride,
+            premultiplied: false,
+        }
+    }
+
+    /// Returns a new color buffer for the given bitmap buffer, validating its layout.
+    ///
+    /// This works like [`new`], except that `BitmapError::StrideTooSmall` is returned if `stride`
+    /// can't fit `pixels_per_row` pixels, and `BitmapError::ByteArrayTooSmall` if `data` can't fit
+    /// `rows` rows.
+    ///
+    /// [`new`]: #method.new
+    pub fn new_checked(
+        format: ColorFormat,
+        depth: BitDepth,
+        rows: u64,
+        pixels_per_row: u64,
+        stride: u64,
+        data: Box<[u8]>,
+    ) -> std::result::Result<BitmapColorBuf, BitmapError> {
+        check_layout(&format, &depth, rows, pixels_per_row, stride, data.len())?;
+
+        Ok(BitmapColorBuf::new(
+            format,
+            depth,
+            rows,
+            pixels_per_row,
+            stride,
+            data,
+        ))
+    }
+
+    /// Returns a new zeroed color buffer with a tightly packed `stride`.
+    ///
+    /// The backing bytes are allocated here, so the returned buffer is ready to be drawn into.
+    /// Every pixel starts out with all of its channels set to `0`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the bitmap in bytes doesn't fit into a `usize`.
+    pub fn empty(
+        format: ColorFormat,
+        depth: BitDepth,
+        rows: u64,
+        pixels_per_row: u64,
+    ) -> BitmapColorBuf {
+        let stride = get_bpp_factor(&format, &depth)
+            .checked_mul(pixels_per_row)
+            .expect("bitmap stride overflows u64");
+        let len = stride
+            .checked_mul(rows)
+            .and_then(|len| usize::try_from(len).ok())
+            .expect("bitmap size overflows usize");
+        let data = vec![0; len].into_boxed_slice();
+
+        BitmapColorBuf::new(format, depth, rows, pixels_per_row, stride, data)
+    }
+
+    /// Copies every pixel of `src` into this buffer.
+    ///
+    /// When both buffers have the same format, depth and premultiplication, the bytes of every
+    /// row are copied as is. Otherwise this works like [`copy_from`]. Both buffers must have the
+    /// same dimensions, otherwise `ColorBufError::InvalidDimensions` is returned.
+    ///
+    /// [`copy_from`]: ../trait.ColorBuf.html#method.copy_from
+    pub fn copy_from_bitmap(&mut self, src: &BitmapColorBuf) -> Result<(), ColorBufError> {
+        if src.dimensions() != self.dimensions() {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        if src.format != self.format
+            || src.depth != self.depth
+            || src.premultiplied != self.premultiplied
+        {
+            return self.copy_from(src);
+        }
+        for y in 0..self.rows {
+            let range = self.get_row_range(y)?;
+            self.data[range].copy_from_slice(src.row_bytes(y)?);
+        }
+        Ok(())
+    }
+
+    /// Returns this buffer set to store its color channels either premultiplied by alpha or not.
+    ///
+    /// Buffers store straight alpha by default. With `premultiplied` set, [`set_pixel`] multiplies
+    /// the color channels by alpha before encoding them and [`get_pixel`] divides them back out,
+    /// which is the layout many GPUs and compositors expect. The existing bytes are not converted.
+    /// This has no effect on formats without an alpha channel.
+    ///
+    /// [`set_pixel`]: ../trait.ColorBuf.html#tymethod.set_pixel
+    /// [`get_pixel`]: ../trait.ColorBuf.html#tymethod.get_pixel
+    pub fn with_premultiplied(self, premultiplied: bool) -> BitmapColorBuf {
+        BitmapColorBuf {
+            premultiplied,
+            ..self
+        }
+    }
+
+    /// Returns whether this buffer stores its color channels premultiplied by alpha.
+    pub fn is_premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+
+    /// Returns the format the pixels are stored in.
+    pub fn format(&self) -> &ColorFormat {
+        &self.format
+    }
+
+    /// Returns the color depth the pixels are stored with.
+    pub fn depth(&self) -> &BitDepth {
+        &self.depth
+    }
+
+    /// Returns how many bytes there are between the starts of two consecutive rows.
+    pub fn stride(&self) -> u64 {
+        self.stride
+    }
+
+    /// Returns every byte backing this buffer, including the padding between rows.
+    ///
+    /// This is the same data given to [`new`], which makes it possible to hand the bitmap to
+    /// other code without copying it.
+    ///
+    /// [`new`]: #method.new
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns every byte backing this buffer for modification, including the padding between
+    /// rows.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Returns the bytes of the pixels on row `y`.
+    ///
+    /// The padding between rows is not included in the returned slice.
+    pub fn row_bytes(&self, y: u64) -> Result<&[u8], ColorBufError> {
+        let range = self.get_row_range(y)?;
+        Ok(&self.data[range])
+    }
+
+    /// Returns the bytes of the pixels on row `y` for modification.
+    ///
+    /// The padding between rows is not included in the returned slice.
+    pub fn row_bytes_mut(&mut self, y: u64) -> Result<&mut [u8], ColorBufError> {
+        let range = self.get_row_range(y)?;
+        Ok(&mut self.data[range])
+    }
+
+    fn get_row_range(&self, y: u64) -> Result<std::ops::Range<usize>, ColorBufError> {
+        if y >= self.rows {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        let len = get_bpp_factor(&self.format, &self.depth).checked_mul(self.pixels_per_row);
+        let range = y
+            .checked_mul(self.stride)
+            .and_then(|start| Some((start, start.checked_add(len?)?)))
+            .and_then(|(start, end)| Some(usize::try_from(start).ok()?..usize::try_from(end).ok()?))
+            .ok_or(ColorBufError::InvalidCoordinate)?;
+        if range.end > self.data.len() {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        Ok(range)
+    }
+
+    /// Returns the index of the first byte of the pixel at `(x, y)`.
+    ///
+    /// Besides checking the coordinates, this makes sure that the whole pixel fits within the
+    /// data, in case the buffer was created with a too small or too large `stride` or `data`.
+    fn get_offset(&self, x: u64, y: u64) -> Result<usize, ColorBufError> {
+        if x >= self.pixels_per_row || y >= self.rows {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        let bpp = get_bpp_factor(&self.format, &self.depth);
+        let index = y
+            .checked_mul(self.stride)
+            .and_then(|row| row.checked_add(bpp.checked_mul(x)?))
+            .and_then(|index| usize::try_from(index).ok())
+            .ok_or(ColorBufError::InvalidCoordinate)?;
+        if bpp as usize > self.data.len().saturating_sub(index) {
+            return Err(ColorBufError::InvalidCoordinate);
         }
+        Ok(index)
     }
 
-    fn get_offset(&self, x: u64, y: u64) -> usize {
+    /// Returns the index of the first byte of the pixel at `(x, y)` without any checks.
+    ///
+    /// Only use this for pixels that are known to fit within the data, e.g. ones that
+    /// `get_offset` has already accepted.
+    fn get_unchecked_offset(&self, x: u64, y: u64) -> usize {
         (y * self.stride + (get_bpp_factor(&self.format, &self.depth) * x)) as usize
     }
+
+    /// Reads the pixel starting at `index`.
+    fn read_pixel(&self, index: usize) -> Color {
+        let color = decode_pixel(&self.data, index, &self.format, &self.depth);
+        if self.premultiplied && has_alpha(&self.format) {
+            color.unpremultiplied()
+        } else {
+            color
+        }
+    }
+
+    /// Writes `color` into the pixel starting at `index`.
+    fn write_pixel(&mut self, index: usize, color: &Color) {
+        let color = self.prepare_color(color);
+        encode_pixel(&mut self.data, index, &self.format, &self.depth, &color);
+    }
+
+    /// Converts `color` into the values to encode for this buffer's format and depth.
+    fn prepare_color(&self, color: &Color) -> Color {
+        let color = clamp_for_depth(color, &self.depth);
+
+        // RGB backings have no alpha channel, so the alpha becomes a divisor of the other color
+        // channels instead, treating the color as premultiplied. Grayscale only stores the
+        // luminance and ignores alpha.
+        match self.format {
+            ColorFormat::RGB | ColorFormat::BGR => {
+                // A fully transparent pixel has no meaningful color, so we just write black
+                // instead of dividing by zero.
+                color.unpremultiplied()
+            }
+            _ if self.premultiplied && has_alpha(&self.format) => color.premultiplied(),
+            _ => color,
+        }
+    }
+}
+
+fn get_channel_size(depth: &BitDepth) -> u64 {
+    match depth {
+        BitDepth::Eight => 1,
+        BitDepth::Sixteen => 2,
+        BitDepth::SixteenFloat => 2,
+        BitDepth::ThirtyTwoFloat => 4,
+    }
+}
+
+fn get_bpp_factor(format: &ColorFormat, depth: &BitDepth) -> u64 {
+    let channels = match &format {
+        ColorFormat::RGBA => 4,
+        ColorFormat::ARGB => 4,
+        ColorFormat::RGB => 3,
+        ColorFormat::BGRA => 4,
+        ColorFormat::BGR => 3,
+        ColorFormat::Grayscale => 1,
+        ColorFormat::GrayscaleAlpha => 2,
+    };
+
+    channels * get_channel_size(depth)
+}
+
+fn has_alpha(format: &ColorFormat) -> bool {
+    match format {
+        ColorFormat::RGBA | ColorFormat::ARGB | ColorFormat::BGRA | ColorFormat::GrayscaleAlpha => {
+            true
+        }
+        ColorFormat::RGB | ColorFormat::BGR | ColorFormat::Grayscale => false,
+    }
+}
+
+/// Reads a single channel starting at `index` within `data`.
+fn read_channel(data: &[u8], index: usize, depth: &BitDepth) -> f32 {
+    match depth {
+        BitDepth::Eight => (data[index] as f32) / 255f32,
+        BitDepth::Sixteen => (u16::from_le_bytes([data[index], data[index + 1]]) as f32) / 65535f32,
+        BitDepth::SixteenFloat => f16_to_f32(u16::from_le_bytes([data[index], data[index + 1]])),
+        BitDepth::ThirtyTwoFloat => f32::from_le_bytes([
+            data[index],
+            data[index + 1],
+            data[index + 2],
+            data[index + 3],
+        ]),
+    }
+}
+
+/// Writes a single channel starting at `index` within `data`.
+fn write_channel(data: &mut [u8], index: usize, depth: &BitDepth, channel: f32) {
+    match depth {
+        BitDepth::Eight => data[index] = to_eight_bit(channel),
+        BitDepth::Sixteen => {
+            data[index..index + 2].copy_from_slice(&to_sixteen_bit(channel).to_le_bytes())
+        }
+        BitDepth::SixteenFloat => {
+            data[index..index + 2].copy_from_slice(&f32_to_f16(channel).to_le_bytes())
+        }
+        BitDepth::ThirtyTwoFloat => data[index..index + 4].copy_from_slice(&channel.to_le_bytes()),
+    }
+}
+
+/// Decodes a row of 8-bit RGBA pixels.
+///
+/// With the `simd` feature, this goes through [`decode_rgba8_row_wide`], otherwise through
+/// [`decode_rgba8_row_scalar`]. Both give the exact same values as [`read_channel`].
+fn decode_rgba8_row(data: &[u8]) -> Vec<Color> {
+    if cfg!(feature = "simd") {
+        decode_rgba8_row_wide(data)
+    } else {
+        decode_rgba8_row_scalar(data)
+    }
+}
+
+/// Decodes a row of 8-bit RGBA pixels one pixel at a time.
+///
+/// The channels are converted through [`EIGHT_BIT_TABLE`], without any branching or division per
+/// channel.
+fn decode_rgba8_row_scalar(data: &[u8]) -> Vec<Color> {
+    data.chunks_exact(4).map(decode_rgba8_pixel).collect()
+}
+
+/// Decodes a row of 8-bit RGBA pixels four pixels at a time.
+///
+/// Every group of pixels is loaded as a whole and its channels are divided in a fixed-size loop,
+/// which the compiler can turn into vector instructions. The pixels left over are decoded like in
+/// [`decode_rgba8_row_scalar`].
+fn decode_rgba8_row_wide(data: &[u8]) -> Vec<Color> {
+    const LANES: usize = 16;
+
+    let mut colors = Vec::with_capacity(data.len() / 4);
+    let mut groups = data.chunks_exact(LANES);
+    for group in &mut groups {
+        let mut lanes = [0f32; LANES];
+        for (lane, &byte) in lanes.iter_mut().zip(group) {
+            *lane = f32::from(byte) / 255f32;
+        }
+        colors.extend(lanes.chunks_exact(4).map(|pixel| Color {
+            r: pixel[0],
+            g: pixel[1],
+            b: pixel[2],
+            a: pixel[3],
+        }));
+    }
+    colors.extend(groups.remainder().chunks_exact(4).map(decode_rgba8_pixel));
+    colors
 }
 
-fn get_bpp_factor(format: &ColorFormat, _depth: &BitDepth) -> u64 {
-    let ret: u64;
+/// Decodes a single 8-bit RGBA pixel through [`EIGHT_BIT_TABLE`].
+fn decode_rgba8_pixel(pixel: &[u8]) -> Color {
+    Color {
+        r: EIGHT_BIT_TABLE[pixel[0] as usize],
+        g: EIGHT_BIT_TABLE[pixel[1] as usize],
+        b: EIGHT_BIT_TABLE[pixel[2] as usize],
+        a: EIGHT_BIT_TABLE[pixel[3] as usize],
+    }
+}
+
+/// The channel value of every 8-bit word.
+static EIGHT_BIT_TABLE: [f32; 256] = {
+    let mut table = [0f32; 256];
+    let mut value = 0;
+    while value < 256 {
+        table[value] = (value as f32) / 255f32;
+        value += 1;
+    }
+    table
+};
 
-    match &format {
-        ColorFormat::RGBA => {
-            ret = 4;
+/// Decodes the pixel starting at `index` within `data`.
+fn decode_pixel(data: &[u8], index: usize, format: &ColorFormat, depth: &BitDepth) -> Color {
+    let size = get_channel_size(depth) as usize;
+    let channel = |n: usize| read_channel(data, index + n * size, depth);
+
+    match format {
+        ColorFormat::RGBA => Color {
+            r: channel(0),
+            g: channel(1),
+            b: channel(2),
+            a: channel(3),
+        },
+        ColorFormat::ARGB => Color {
+            a: channel(0),
+            r: channel(1),
+            g: channel(2),
+            b: channel(3),
+        },
+        ColorFormat::RGB => Color {
+            r: channel(0),
+            g: channel(1),
+            b: channel(2),
+            a: 1.0f32,
+        },
+        ColorFormat::BGRA => Color {
+            b: channel(0),
+            g: channel(1),
+            r: channel(2),
+            a: channel(3),
+        },
+        ColorFormat::BGR => Color {
+            b: channel(0),
+            g: channel(1),
+            r: channel(2),
+            a: 1.0f32,
+        },
+        ColorFormat::Grayscale => {
+            let luminance = channel(0);
+            Color {
+                r: luminance,
+                g: luminance,
+                b: luminance,
+                a: 1.0f32,
+            }
         }
-        ColorFormat::ARGB => {
-            ret = 4;
+        ColorFormat::GrayscaleAlpha => {
+            let luminance = channel(0);
+            Color {
+                r: luminance,
+                g: luminance,
+                b: luminance,
+                a: channel(1),
+            }
         }
-        ColorFormat::RGB => {
-            ret = 3;
+    }
+}
+
+/// Encodes `color` into the pixel starting at `index` within `data`.
+///
+/// Formats without an alpha channel simply drop it, while grayscale formats store the luminance
+/// of the color.
+fn encode_pixel(
+    data: &mut [u8],
+    index: usize,
+    format: &ColorFormat,
+    depth: &BitDepth,
+    color: &Color,
+) {
+    let size = get_channel_size(depth) as usize;
+    let luminance = color.to_grayscale().r;
+    let channels: &[f32] = match format {
+        ColorFormat::RGBA => &[color.r, color.g, color.b, color.a],
+        ColorFormat::ARGB => &[color.a, color.r, color.g, color.b],
+        ColorFormat::RGB => &[color.r, color.g, color.b],
+        ColorFormat::BGRA => &[color.b, color.g, color.r, color.a],
+        ColorFormat::BGR => &[color.b, color.g, color.r],
+        ColorFormat::Grayscale => &[luminance],
+        ColorFormat::GrayscaleAlpha => &[luminance, color.a],
+    };
+
+    for (n, channel) in channels.iter().enumerate() {
+        write_channel(data, index + n * size, depth, *channel);
+    }
+}
+
+/// Converts a channel value into a byte, rounding to the nearest value.
+///
+/// Values outside of `[0, 1]` are saturated instead of wrapping around.
+fn to_eight_bit(channel: f32) -> u8 {
+    (channel * 255f32 + 0.5f32).clamp(0f32, 255f32) as u8
+}
+
+/// Converts a channel value into a 16-bit word, rounding to the nearest value.
+///
+/// Values outside of `[0, 1]` are saturated instead of wrapping around.
+fn to_sixteen_bit(channel: f32) -> u16 {
+    (channel * 65535f32 + 0.5f32).clamp(0f32, 65535f32) as u16
+}
+
+/// Clamps `color` to `[0, 1]`, unless `depth` can store values outside of it.
+fn clamp_for_depth(color: &Color, depth: &BitDepth) -> Color {
+    match depth {
+        BitDepth::Eight | BitDepth::Sixteen => color.clamped(),
+        BitDepth::SixteenFloat | BitDepth::ThirtyTwoFloat => *color,
+    }
+}
+
+/// Converts the bits of a half precision float into an `f32`.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1F) as u32;
+    let mantissa = (bits & 0x3FF) as u32;
+
+    let magnitude = match exponent {
+        // Subnormal numbers are normal ones in `f32`, so just compute the value.
+        0 => {
+            let value = mantissa as f32 / (1 << 24) as f32;
+            return if sign == 0 { value } else { -value };
         }
+        0x1F => 0x7F80_0000 | (mantissa << 13),
+        _ => ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(sign | magnitude)
+}
+
+/// Converts an `f32` into the bits of a half precision float, rounding to the nearest even value.
+///
+/// Values too large for a half precision float become infinities.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    if exponent == 0xFF {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7C00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
     }
 
-    ret
+    let (half, remainder, halfway) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        (
+            mantissa >> shift,
+            mantissa & ((1 << shift) - 1),
+            1 << (shift - 1),
+        )
+    } else {
+        (
+            ((exponent as u32) << 10) | (mantissa >> 13),
+            mantissa & 0x1FFF,
+            0x1000,
+        )
+    };
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity.
+    let rounded = if remainder > halfway || (remainder == halfway && half & 1 == 1) {
+        half + 1
+    } else {
+        half
+    };
+    sign | rounded as u16
 }
 
 #[derive(Debug, PartialEq)]
 pub enum BitmapError {
     ByteArrayTooSmall,
+    StrideTooSmall,
+    /// Reading a pixel from the source [`ColorBuf`] failed.
+    ///
+    /// [`ColorBuf`]: ../trait.ColorBuf.html
+    SourceError(ColorBufError),
+}
+
+impl fmt::Display for BitmapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BitmapError::ByteArrayTooSmall => write!(f, "byte array is too small for the bitmap"),
+            BitmapError::StrideTooSmall => write!(f, "stride is too small for a row of pixels"),
+            BitmapError::SourceError(error) => write!(f, "reading the source failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for BitmapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BitmapError::SourceError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Checks that a bitmap with the given layout fits within `len` bytes.
+fn check_layout(
+    format: &ColorFormat,
+    depth: &BitDepth,
+    rows: u64,
+    pixels_per_row: u64,
+    stride: u64,
+    len: usize,
+) -> std::result::Result<(), BitmapError> {
+    // A row too large to count in a `u64` can't fit in any stride.
+    match get_bpp_factor(format, depth).checked_mul(pixels_per_row) {
+        Some(row_len) if row_len <= stride => {}
+        _ => return Err(BitmapError::StrideTooSmall),
+    }
+
+    // Likewise, a bitmap too large to count in a `usize` can't fit in any byte array.
+    match rows
+        .checked_mul(stride)
+        .and_then(|req_len| usize::try_from(req_len).ok())
+    {
+        Some(req_len) if req_len <= len => Ok(()),
+        _ => Err(BitmapError::ByteArrayTooSmall),
+    }
 }
 
 /// Writes the given [`ColorBuf`] to a bitmap
@@ -214,68 +781,115 @@ pub enum BitmapError {
 /// `output` using a certain `format` and `depth`, returning the `stride`
 /// of the output.
 ///
+/// Every row gets room for four channels per pixel, i.e. the stride is `4 * width` bytes for
+/// 8-bit depths, whatever the `format`. Use [`to_bitmap_with_stride`] for other row layouts.
+///
 /// User must make sure that the given byte slice is aligned to, e.g. 32-bit boundary, if required
 /// by the consumer of the resulting bitmap.
 ///
 /// Using the `output` if this function failed is a programmer error.
-pub fn to_bitmap<'a, B>(
+///
+/// [`to_bitmap_with_stride`]: fn.to_bitmap_with_stride.html
+pub fn to_bitmap<B>(
     buf: B,
     format: ColorFormat,
     depth: BitDepth,
     stride: &mut u64,
-    output: &'a mut [u8],
+    output: &mut [u8],
 ) -> std::result::Result<(), BitmapError>
 where
     B: ColorBuf,
 {
     // We often want this stuff to be aligned at 32-bit boundary.
     // FIXME: Do this better
-    *stride = 4 * buf.get_width();
+    *stride = (4 * get_channel_size(&depth))
+        .checked_mul(buf.get_width())
+        .ok_or(BitmapError::ByteArrayTooSmall)?;
 
-    let req_bitmap_len: usize = buf.get_height() as usize * (*stride as usize);
-    if req_bitmap_len > output.len() {
-        return Err(BitmapError::ByteArrayTooSmall);
-    }
+    to_bitmap_with_stride(buf, format, depth, *stride, output)
+}
+
+/// Writes the given [`ColorBuf`] to a bitmap with the given `stride`.
+///
+/// This function works like [`to_bitmap`], except that the caller decides how many bytes there
+/// are between rows. The `stride` must be at least as large as the width of the buffer times the
+/// amount of bytes per pixel for the given `format` and `depth`.
+///
+/// Using the `output` if this function failed is a programmer error.
+///
+/// [`ColorBuf`]: ../trait.ColorBuf.html
+/// [`to_bitmap`]: fn.to_bitmap.html
+pub fn to_bitmap_with_stride<B>(
+    buf: B,
+    format: ColorFormat,
+    depth: BitDepth,
+    stride: u64,
+    output: &mut [u8],
+) -> std::result::Result<(), BitmapError>
+where
+    B: ColorBuf,
+{
+    check_layout(
+        &format,
+        &depth,
+        buf.get_height(),
+        buf.get_width(),
+        stride,
+        output.len(),
+    )?;
 
     for y in 0..buf.get_height() {
         for x in 0..buf.get_width() {
-            let color: Color = buf.get_pixel(x, y).unwrap();
-            let index: usize = (y * (*stride) + (get_bpp_factor(&format, &depth) * x)) as usize;
-
-            match depth {
-                BitDepth::Eight => {
-                    let r_byte = (color.r * 255f32) as u8;
-                    let g_byte = (color.g * 255f32) as u8;
-                    let b_byte = (color.b * 255f32) as u8;
-                    let a_byte = (color.a * 255f32) as u8;
-
-                    match format {
-                        ColorFormat::RGBA => {
-                            output[index] = r_byte;
-                            output[index + 1] = g_byte;
-                            output[index + 2] = b_byte;
-                            output[index + 3] = a_byte;
-                        }
-                        ColorFormat::ARGB => {
-                            output[index] = a_byte;
-                            output[index + 1] = r_byte;
-                            output[index + 2] = g_byte;
-                            output[index + 3] = b_byte;
-                        }
-                        ColorFormat::RGB => {
-                            output[index] = r_byte;
-                            output[index + 1] = g_byte;
-                            output[index + 2] = b_byte;
-                        }
-                    }
-                }
-            }
+            let color = clamp_for_depth(
+                &buf.get_pixel(x, y).map_err(BitmapError::SourceError)?,
+                &depth,
+            );
+            let index: usize = (y * stride + (get_bpp_factor(&format, &depth) * x)) as usize;
+
+            encode_pixel(output, index, &format, &depth, &color);
         }
     }
     Ok(())
 }
 
+/// Reads a bitmap into a [`VecColorBuf`].
+///
+/// This function decodes every pixel of `data`, which is laid out according to `format` and
+/// `depth`, into an owned buffer that doesn't depend on the bitmap anymore.
+///
+/// # Arguments
+///
+/// * `data` - The bitmap image.
+/// * `format` - The format the data is in.
+/// * `depth` - The color depth of the data in the format.
+/// * `rows` - How many rows this bitmap image has?
+/// * `pixels_per_row` - The width of the image.
+/// * `stride` - How many bytes are between rows?
+///
+/// [`VecColorBuf`]: ../vec/struct.VecColorBuf.html
+pub fn from_bitmap(
+    data: &[u8],
+    format: ColorFormat,
+    depth: BitDepth,
+    rows: u64,
+    pixels_per_row: u64,
+    stride: u64,
+) -> std::result::Result<VecColorBuf, BitmapError> {
+    check_layout(&format, &depth, rows, pixels_per_row, stride, data.len())?;
+
+    let mut buf = VecColorBuf::new(pixels_per_row, rows);
+    for y in 0..rows {
+        for x in 0..pixels_per_row {
+            let index: usize = (y * stride + (get_bpp_factor(&format, &depth) * x)) as usize;
+            let color = decode_pixel(data, index, &format, &depth);
+            buf.set_pixel(x, y, &color).unwrap();
+        }
+    }
+    Ok(buf)
+}
+
 #[cfg(test)]
+#[allow(clippy::clone_on_copy)]
 mod tests {
     use super::*;
 
@@ -380,4 +994,1060 @@ mod tests {
         assert_eq!(8, stride);
         assert_eq!(orig_bitmap, new_bitmap);
     }
+
+    #[test]
+    fn transparent_to_rgb() {
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            1,
+            1,
+            3,
+            Box::new([0xFFu8, 0xFF, 0xFF]),
+        );
+        colorbuf
+            .set_pixel(
+                0,
+                0,
+                &Color {
+                    r: 1f32,
+                    g: 0.5f32,
+                    b: 0.25f32,
+                    a: 0f32,
+                },
+            )
+            .unwrap();
+
+        assert_eq!([0x00u8, 0x00, 0x00], *colorbuf.data);
+    }
+
+    #[test]
+    fn rounding() {
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            1,
+            1,
+            4,
+            Box::new([0x00u8; 4]),
+        );
+        colorbuf
+            .set_pixel(
+                0,
+                0,
+                &Color {
+                    r: 0.5f32,
+                    g: 1.2f32,
+                    b: 0f32,
+                    a: 1f32,
+                },
+            )
+            .unwrap();
+
+        assert_eq!([128u8, 255, 0, 255], *colorbuf.data);
+
+        let mut new_bitmap = [0x00u8; 4];
+        let mut stride = 0;
+        to_bitmap(
+            colorbuf,
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            &mut stride,
+            &mut new_bitmap,
+        )
+        .unwrap();
+
+        assert_eq!([128u8, 255, 0, 255], new_bitmap);
+    }
+
+    #[test]
+    fn out_of_range_channels() {
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            1,
+            1,
+            4,
+            Box::new([0x00u8; 4]),
+        );
+        colorbuf
+            .set_pixel(
+                0,
+                0,
+                &Color {
+                    r: -0.1f32,
+                    g: 0f32,
+                    b: 1f32,
+                    a: 1.5f32,
+                },
+            )
+            .unwrap();
+
+        assert_eq!([0u8, 0, 255, 255], *colorbuf.data);
+    }
+
+    #[test]
+    fn packed_rgb_with_stride() {
+        // RGB. 2x2 image with first pixel being red, second green, third blue, and fourth white
+        let orig_bitmap = [
+            0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+        let colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            2,
+            2,
+            6,
+            Box::new(orig_bitmap),
+        );
+        let mut new_bitmap = [0x00u8; 12];
+        to_bitmap_with_stride(
+            colorbuf,
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            6,
+            &mut new_bitmap,
+        )
+        .unwrap();
+
+        assert_eq!(orig_bitmap, new_bitmap);
+    }
+
+    #[test]
+    fn too_small_stride() {
+        let colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            1,
+            2,
+            6,
+            Box::new([0x00u8; 6]),
+        );
+        let mut new_bitmap = [0x00u8; 6];
+
+        assert_eq!(
+            Err(BitmapError::StrideTooSmall),
+            to_bitmap_with_stride(
+                colorbuf,
+                ColorFormat::RGB,
+                BitDepth::Eight,
+                5,
+                &mut new_bitmap,
+            )
+        );
+    }
+
+    #[test]
+    fn decode_bitmap() {
+        // ARGB. 2x1 image with 4 bytes of padding. First pixel is red, second transparent blue.
+        let orig_bitmap = [
+            0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let colorbuf =
+            from_bitmap(&orig_bitmap, ColorFormat::ARGB, BitDepth::Eight, 1, 2, 12).unwrap();
+
+        assert_eq!(2, colorbuf.get_width());
+        assert_eq!(1, colorbuf.get_height());
+        assert_eq!(
+            Color {
+                r: 1f32,
+                g: 0f32,
+                b: 0f32,
+                a: 1f32,
+            },
+            colorbuf.get_pixel(0, 0).unwrap()
+        );
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 1f32,
+                a: 0f32,
+            },
+            colorbuf.get_pixel(1, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn decode_too_small_bitmap() {
+        let orig_bitmap = [0x00u8; 7];
+
+        assert_eq!(
+            Err(BitmapError::ByteArrayTooSmall),
+            from_bitmap(&orig_bitmap, ColorFormat::RGBA, BitDepth::Eight, 1, 2, 8,)
+        );
+    }
+
+    #[test]
+    fn sixteen_bit_roundtrip() {
+        // RGBA. A single pixel with every channel being distinct.
+        let orig_bitmap = [0x34, 0x12, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x80];
+        let colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Sixteen,
+            1,
+            1,
+            8,
+            Box::new(orig_bitmap),
+        );
+
+        assert_eq!(
+            Color {
+                r: 0x1234 as f32 / 65535f32,
+                g: 1f32,
+                b: 0f32,
+                a: 0x8000 as f32 / 65535f32,
+            },
+            colorbuf.get_pixel(0, 0).unwrap()
+        );
+
+        let mut new_bitmap = [0x00u8; 8];
+        let mut stride = 0;
+        to_bitmap(
+            colorbuf,
+            ColorFormat::RGBA,
+            BitDepth::Sixteen,
+            &mut stride,
+            &mut new_bitmap,
+        )
+        .unwrap();
+
+        assert_eq!(orig_bitmap, new_bitmap);
+    }
+
+    #[test]
+    fn bgra_roundtrip() {
+        let color = Color {
+            r: 1f32,
+            g: 0.2f32,
+            b: 0f32,
+            a: 0.6f32,
+        };
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::BGRA,
+            BitDepth::Eight,
+            1,
+            1,
+            4,
+            Box::new([0x00u8; 4]),
+        );
+        colorbuf.set_pixel(0, 0, &color).unwrap();
+
+        assert_eq!([0x00u8, 0x33, 0xFF, 0x99], *colorbuf.data);
+        assert_eq!(color, colorbuf.get_pixel(0, 0).unwrap());
+    }
+
+    #[test]
+    fn bgr_to_bitmap() {
+        // RGB. A single orange pixel.
+        let orig_bitmap = [0xFF, 0x80, 0x00];
+        let colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            1,
+            1,
+            3,
+            Box::new(orig_bitmap),
+        );
+        let mut new_bitmap = [0x00u8; 3];
+        to_bitmap_with_stride(
+            colorbuf,
+            ColorFormat::BGR,
+            BitDepth::Eight,
+            3,
+            &mut new_bitmap,
+        )
+        .unwrap();
+
+        assert_eq!([0x00u8, 0x80, 0xFF], new_bitmap);
+    }
+
+    #[test]
+    fn fill() {
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            3,
+            3,
+            12,
+            Box::new([0x00u8; 36]),
+        );
+        colorbuf
+            .fill(&Color {
+                r: 0f32,
+                g: 1f32,
+                b: 0f32,
+                a: 1f32,
+            })
+            .unwrap();
+
+        assert_eq!([0x00u8, 0xFF, 0x00], colorbuf.data[0..3]);
+        assert_eq!([0x00u8, 0xFF, 0x00], colorbuf.data[15..18]);
+        assert_eq!([0x00u8, 0xFF, 0x00], colorbuf.data[30..33]);
+        assert_eq!([0x00u8; 3], colorbuf.data[33..36]);
+    }
+
+    #[test]
+    fn new_checked_overflowing() {
+        assert_eq!(
+            Some(BitmapError::StrideTooSmall),
+            BitmapColorBuf::new_checked(
+                ColorFormat::RGBA,
+                BitDepth::Eight,
+                1,
+                u64::MAX / 2,
+                u64::MAX,
+                Box::new([0x00u8; 16]),
+            )
+            .err()
+        );
+        assert_eq!(
+            Some(BitmapError::ByteArrayTooSmall),
+            BitmapColorBuf::new_checked(
+                ColorFormat::RGBA,
+                BitDepth::Eight,
+                u64::MAX / 4,
+                2,
+                8,
+                Box::new([0x00u8; 16]),
+            )
+            .err()
+        );
+    }
+
+    #[test]
+    fn overflowing_stride() {
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            2,
+            2,
+            u64::MAX / 2,
+            Box::new([0x00u8; 16]),
+        );
+        assert_eq!(Color::TRANSPARENT, colorbuf.get_pixel(1, 0).unwrap());
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            colorbuf.get_pixel(0, 1)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            colorbuf.set_pixel(0, 1, &Color::RED)
+        );
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), colorbuf.get_row(1));
+    }
+
+    #[test]
+    fn new_checked() {
+        assert!(BitmapColorBuf::new_checked(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            2,
+            2,
+            8,
+            Box::new([0x00u8; 16]),
+        )
+        .is_ok());
+        assert_eq!(
+            Some(BitmapError::ByteArrayTooSmall),
+            BitmapColorBuf::new_checked(
+                ColorFormat::RGBA,
+                BitDepth::Eight,
+                2,
+                2,
+                8,
+                Box::new([0x00u8; 15]),
+            )
+            .err()
+        );
+        assert_eq!(
+            Some(BitmapError::StrideTooSmall),
+            BitmapColorBuf::new_checked(
+                ColorFormat::RGBA,
+                BitDepth::Eight,
+                2,
+                2,
+                7,
+                Box::new([0x00u8; 16]),
+            )
+            .err()
+        );
+    }
+
+    #[test]
+    fn too_small_stride_access() {
+        // RGBA. 2x2 image whose stride only fits a single pixel.
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            2,
+            2,
+            4,
+            Box::new([0x00u8; 8]),
+        );
+
+        assert!(colorbuf.get_pixel(0, 1).is_ok());
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            colorbuf.get_pixel(1, 1)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            colorbuf.set_pixel(
+                1,
+                1,
+                &Color {
+                    r: 1f32,
+                    g: 1f32,
+                    b: 1f32,
+                    a: 1f32,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn failing_source() {
+        struct FailingColorBuf;
+
+        impl ColorBuf for FailingColorBuf {
+            fn get_pixel(&self, x: u64, y: u64) -> Result<Color, ColorBufError> {
+                if x == 1 && y == 0 {
+                    return Err(ColorBufError::InvalidCoordinate);
+                }
+                Ok(Color {
+                    r: 0f32,
+                    g: 0f32,
+                    b: 0f32,
+                    a: 1f32,
+                })
+            }
+
+            fn set_pixel(&mut self, _x: u64, _y: u64, _color: &Color) -> Result<(), ColorBufError> {
+                Ok(())
+            }
+
+            fn get_width(&self) -> u64 {
+                2
+            }
+
+            fn get_height(&self) -> u64 {
+                1
+            }
+        }
+
+        let mut new_bitmap = [0x00u8; 8];
+        let mut stride = 0;
+
+        assert_eq!(
+            Err(BitmapError::SourceError(ColorBufError::InvalidCoordinate)),
+            to_bitmap(
+                FailingColorBuf,
+                ColorFormat::RGBA,
+                BitDepth::Eight,
+                &mut stride,
+                &mut new_bitmap,
+            )
+        );
+    }
+
+    #[test]
+    fn grayscale_roundtrip() {
+        // Grayscale. 2x2 image with a row of padding bytes.
+        let orig_bitmap = [0x00, 0x40, 0x00, 0x80, 0xFF, 0x00];
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::Grayscale,
+            BitDepth::Eight,
+            2,
+            2,
+            3,
+            Box::new(orig_bitmap),
+        );
+
+        assert_eq!(
+            Color {
+                r: 0x40 as f32 / 255f32,
+                g: 0x40 as f32 / 255f32,
+                b: 0x40 as f32 / 255f32,
+                a: 1f32,
+            },
+            colorbuf.get_pixel(1, 0).unwrap()
+        );
+
+        colorbuf
+            .set_pixel(
+                0,
+                0,
+                &Color {
+                    r: 0f32,
+                    g: 1f32,
+                    b: 0f32,
+                    a: 1f32,
+                },
+            )
+            .unwrap();
+        assert_eq!(0xB6, colorbuf.data[0]);
+
+        let mut new_bitmap = [0x00u8; 6];
+        to_bitmap_with_stride(
+            colorbuf,
+            ColorFormat::Grayscale,
+            BitDepth::Eight,
+            3,
+            &mut new_bitmap,
+        )
+        .unwrap();
+
+        assert_eq!([0xB6, 0x40, 0x00, 0x80, 0xFF, 0x00], new_bitmap);
+    }
+
+    #[test]
+    fn translucent_without_alpha_channel() {
+        let color = Color {
+            r: 0.2f32,
+            g: 0.2f32,
+            b: 0.2f32,
+            a: 0.5f32,
+        };
+
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::Grayscale,
+            BitDepth::Eight,
+            1,
+            1,
+            1,
+            Box::new([0x00]),
+        );
+        colorbuf.set_pixel(0, 0, &color).unwrap();
+        assert_eq!([0x33], colorbuf.as_bytes());
+
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            1,
+            1,
+            3,
+            Box::new([0x00; 3]),
+        );
+        colorbuf.set_pixel(0, 0, &color).unwrap();
+        assert_eq!([0x66, 0x66, 0x66], colorbuf.as_bytes());
+
+        colorbuf.set_pixel(0, 0, &Color::TRANSPARENT).unwrap();
+        assert_eq!([0x00, 0x00, 0x00], colorbuf.as_bytes());
+    }
+
+    #[test]
+    fn grayscale_alpha_roundtrip() {
+        let gray = Color {
+            r: 0.6f32,
+            g: 0.6f32,
+            b: 0.6f32,
+            a: 0.4f32,
+        };
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::GrayscaleAlpha,
+            BitDepth::Eight,
+            1,
+            2,
+            4,
+            Box::new([0x00, 0x00, 0xFF, 0xFF]),
+        );
+        colorbuf.set_pixel(0, 0, &gray).unwrap();
+
+        assert_eq!([0x99u8, 0x66, 0xFF, 0xFF], *colorbuf.data);
+        assert_eq!(gray, colorbuf.get_pixel(0, 0).unwrap());
+
+        let mut new_bitmap = [0x00u8; 4];
+        to_bitmap_with_stride(
+            colorbuf,
+            ColorFormat::GrayscaleAlpha,
+            BitDepth::Eight,
+            4,
+            &mut new_bitmap,
+        )
+        .unwrap();
+
+        assert_eq!([0x99u8, 0x66, 0xFF, 0xFF], new_bitmap);
+    }
+
+    #[test]
+    fn unchecked_access() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            2,
+            2,
+            8,
+            Box::new([0x00u8; 16]),
+        );
+
+        unsafe {
+            colorbuf.set_pixel_unchecked(1, 1, &red);
+            assert_eq!(red, colorbuf.get_pixel_unchecked(1, 1));
+        }
+        assert_eq!(red, colorbuf.get_pixel(1, 1).unwrap());
+    }
+
+    #[test]
+    fn row_bytes() {
+        // RGB. 2x2 image with two bytes of padding per row.
+        let orig_bitmap = [
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0xAA, 0xAA, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C,
+            0xAA, 0xAA,
+        ];
+        let mut colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            2,
+            2,
+            8,
+            Box::new(orig_bitmap),
+        );
+
+        assert_eq!(
+            [0x07u8, 0x08, 0x09, 0x0A, 0x0B, 0x0C],
+            colorbuf.row_bytes(1).unwrap()
+        );
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), colorbuf.row_bytes(2));
+
+        colorbuf.row_bytes_mut(0).unwrap()[3] = 0xFF;
+        assert_eq!(
+            Color {
+                r: 1f32,
+                g: 5f32 / 255f32,
+                b: 6f32 / 255f32,
+                a: 1f32,
+            },
+            colorbuf.get_pixel(1, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn dimensions() {
+        let colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            2,
+            3,
+            9,
+            Box::new([0x00u8; 18]),
+        );
+
+        assert_eq!(
+            (colorbuf.get_width(), colorbuf.get_height()),
+            colorbuf.dimensions()
+        );
+        assert_eq!((3, 2), colorbuf.dimensions());
+        assert_eq!(Some(6), colorbuf.pixel_count());
+
+        let huge = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            u64::MAX,
+            2,
+            8,
+            Box::new([]),
+        );
+        assert_eq!(None, huge.pixel_count());
+    }
+
+    #[test]
+    fn errors() {
+        let errors: Vec<Box<dyn std::error::Error>> = vec![
+            Box::new(BitmapError::ByteArrayTooSmall),
+            Box::new(BitmapError::StrideTooSmall),
+            Box::new(BitmapError::SourceError(ColorBufError::InvalidCoordinate)),
+        ];
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(
+            vec![
+                "byte array is too small for the bitmap",
+                "stride is too small for a row of pixels",
+                "reading the source failed: pixel coordinate is out of bounds",
+            ],
+            messages
+        );
+        assert!(errors[0].source().is_none());
+        assert!(errors[2].source().is_some());
+    }
+
+    #[test]
+    fn empty() {
+        let colorbuf = BitmapColorBuf::empty(ColorFormat::RGB, BitDepth::Sixteen, 3, 5);
+
+        assert_eq!(30, colorbuf.stride);
+        assert_eq!(90, colorbuf.data.len());
+        assert_eq!(5, colorbuf.get_width());
+        assert_eq!(3, colorbuf.get_height());
+        for y in 0..3 {
+            for x in 0..5 {
+                assert_eq!(Color::BLACK, colorbuf.get_pixel(x, y).unwrap());
+            }
+        }
+
+        let colorbuf = BitmapColorBuf::empty(ColorFormat::RGBA, BitDepth::Eight, 2, 2);
+        assert_eq!(16, colorbuf.data.len());
+        assert_eq!(Color::TRANSPARENT, colorbuf.get_pixel(1, 1).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "bitmap stride overflows u64")]
+    fn empty_overflowing_stride() {
+        BitmapColorBuf::empty(ColorFormat::RGBA, BitDepth::Eight, 1, u64::MAX / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "bitmap size overflows usize")]
+    fn empty_overflowing_size() {
+        BitmapColorBuf::empty(ColorFormat::RGBA, BitDepth::Eight, u64::MAX / 4, 2);
+    }
+
+    #[test]
+    fn premultiplied_roundtrip() {
+        let color = Color {
+            r: 1f32,
+            g: 0.6f32,
+            b: 0f32,
+            a: 0.5f32,
+        };
+        let mut straight = BitmapColorBuf::empty(ColorFormat::RGBA, BitDepth::Eight, 1, 1);
+        let mut premultiplied = BitmapColorBuf::empty(ColorFormat::RGBA, BitDepth::Eight, 1, 1)
+            .with_premultiplied(true);
+        straight.set_pixel(0, 0, &color).unwrap();
+        premultiplied.set_pixel(0, 0, &color).unwrap();
+
+        assert!(!straight.is_premultiplied());
+        assert!(premultiplied.is_premultiplied());
+        assert_eq!(&[0xFF, 0x99, 0x00, 0x80], straight.row_bytes(0).unwrap());
+        assert_eq!(
+            &[0x80, 0x4D, 0x00, 0x80],
+            premultiplied.row_bytes(0).unwrap()
+        );
+
+        let straight = straight.get_pixel(0, 0).unwrap();
+        let premultiplied = premultiplied.get_pixel(0, 0).unwrap();
+        assert!((straight.r - premultiplied.r).abs() < 1f32 / 128f32);
+        assert!((straight.g - premultiplied.g).abs() < 1f32 / 128f32);
+        assert_eq!(straight.b, premultiplied.b);
+        assert_eq!(straight.a, premultiplied.a);
+    }
+
+    #[test]
+    fn rgba8_row_fast_path() {
+        let data: Vec<u8> = (0..=255).collect();
+        let fast = decode_rgba8_row(&data);
+
+        assert_eq!(64, fast.len());
+        for (x, color) in fast.iter().enumerate() {
+            let scalar = decode_pixel(&data, x * 4, &ColorFormat::RGBA, &BitDepth::Eight);
+            assert_eq!(scalar.r.to_bits(), color.r.to_bits());
+            assert_eq!(scalar.g.to_bits(), color.g.to_bits());
+            assert_eq!(scalar.b.to_bits(), color.b.to_bits());
+            assert_eq!(scalar.a.to_bits(), color.a.to_bits());
+        }
+    }
+
+    #[test]
+    fn rgba8_row_wide_matches_scalar() {
+        // Every byte in every lane, with row lengths that leave some pixels over.
+        let data: Vec<u8> = (0..=255).chain(0..=255).rev().collect();
+        for offset in 0..4 {
+            for pixels in 0..=(data.len() - offset * 4) / 4 {
+                let row = &data[offset * 4..offset * 4 + pixels * 4];
+                let wide = decode_rgba8_row_wide(row);
+                let scalar = decode_rgba8_row_scalar(row);
+
+                assert_eq!(pixels, wide.len());
+                for (wide, scalar) in wide.iter().zip(scalar.iter()) {
+                    assert_eq!(scalar.r.to_bits(), wide.r.to_bits());
+                    assert_eq!(scalar.g.to_bits(), wide.g.to_bits());
+                    assert_eq!(scalar.b.to_bits(), wide.b.to_bits());
+                    assert_eq!(scalar.a.to_bits(), wide.a.to_bits());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn eight_bit_table() {
+        for (value, channel) in EIGHT_BIT_TABLE.iter().enumerate() {
+            assert_eq!(
+                read_channel(&[value as u8], 0, &BitDepth::Eight).to_bits(),
+                channel.to_bits()
+            );
+        }
+    }
+
+    #[test]
+    fn to_color_vec_matches_get_pixel() {
+        for format in [ColorFormat::RGBA, ColorFormat::BGR] {
+            let data: Vec<u8> = (0..=255).cycle().take(4 * 40).collect();
+            let colorbuf =
+                BitmapColorBuf::new(format, BitDepth::Eight, 4, 10, 40, data.into_boxed_slice())
+                    .with_premultiplied(true);
+            let colors = colorbuf.to_color_vec();
+            let rows: Vec<Vec<Color>> = crate::ops::iter_rows(&colorbuf)
+                .map(Result::unwrap)
+                .collect();
+
+            assert_eq!(40, colors.len());
+            assert_eq!(4, rows.len());
+            for y in 0..4 {
+                for x in 0..10 {
+                    let color = colorbuf.get_pixel(x, y).unwrap();
+                    assert_eq!(color, colors[(y * 10 + x) as usize]);
+                    assert_eq!(color, rows[y as usize][x as usize]);
+                }
+            }
+            assert_eq!(Err(ColorBufError::InvalidCoordinate), colorbuf.get_row(4));
+        }
+    }
+
+    #[test]
+    fn half_float_codec() {
+        assert_eq!(0x3C00, f32_to_f16(1f32));
+        assert_eq!(0xC000, f32_to_f16(-2f32));
+        assert_eq!(0x7BFF, f32_to_f16(65504f32));
+        assert_eq!(0x7C00, f32_to_f16(65536f32));
+        assert_eq!(0x0001, f32_to_f16(2f32.powi(-24)));
+        assert_eq!(0x0000, f32_to_f16(2f32.powi(-26)));
+        // Exactly halfway between two values rounds to the even one.
+        assert_eq!(0x3C00, f32_to_f16(1f32 + 2f32.powi(-11)));
+        assert_eq!(0x3C02, f32_to_f16(1f32 + 3f32 * 2f32.powi(-11)));
+
+        for bits in (0..0x7C00).chain(0x8000..0xFC00) {
+            assert_eq!(bits, f32_to_f16(f16_to_f32(bits)));
+        }
+        assert!(f16_to_f32(0x7E00).is_nan());
+        assert_eq!(f32::INFINITY, f16_to_f32(0x7C00));
+    }
+
+    #[test]
+    fn half_float_roundtrip() {
+        let hdr = Color {
+            r: 2.5f32,
+            g: -0.25f32,
+            b: 0.1f32,
+            a: 1f32,
+        };
+        let mut colorbuf = BitmapColorBuf::empty(ColorFormat::RGBA, BitDepth::SixteenFloat, 1, 2);
+        colorbuf.set_pixel(1, 0, &hdr).unwrap();
+
+        assert_eq!(&[0x00, 0x41], &colorbuf.row_bytes(0).unwrap()[8..10]);
+        let read = colorbuf.get_pixel(1, 0).unwrap();
+        assert_eq!(2.5f32, read.r);
+        assert_eq!(-0.25f32, read.g);
+        assert!((read.b - 0.1f32).abs() < 1e-4f32);
+        assert_eq!(1f32, read.a);
+    }
+
+    #[test]
+    fn float_roundtrip() {
+        let hdr = Color {
+            r: 12.345f32,
+            g: -0.3f32,
+            b: 0.1f32,
+            a: 1.5f32,
+        };
+        let mut colorbuf = BitmapColorBuf::empty(ColorFormat::RGBA, BitDepth::ThirtyTwoFloat, 2, 1);
+        colorbuf.set_pixel(0, 1, &hdr).unwrap();
+
+        assert_eq!(16, colorbuf.stride);
+        assert_eq!(
+            &1.5f32.to_le_bytes(),
+            &colorbuf.row_bytes(1).unwrap()[12..16]
+        );
+        assert_eq!(hdr, colorbuf.get_pixel(0, 1).unwrap());
+        assert_eq!(Color::TRANSPARENT, colorbuf.get_pixel(0, 0).unwrap());
+    }
+
+    #[test]
+    fn as_bytes() {
+        let data = [0x10, 0x20, 0x30, 0x00, 0x40, 0x50, 0x60, 0x00];
+        let mut colorbuf =
+            BitmapColorBuf::new(ColorFormat::RGB, BitDepth::Eight, 2, 1, 4, Box::new(data));
+
+        assert_eq!(&data, colorbuf.as_bytes());
+        colorbuf.as_bytes_mut()[4] = 0xFF;
+        assert_eq!(
+            Color::from_rgba8(0xFF, 0x50, 0x60, 0xFF),
+            colorbuf.get_pixel(0, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn stride() {
+        let colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            2,
+            1,
+            8,
+            Box::new([0; 16]),
+        );
+
+        assert_eq!(8, colorbuf.stride());
+        assert_eq!(
+            6,
+            BitmapColorBuf::empty(ColorFormat::BGR, BitDepth::Eight, 1, 2).stride()
+        );
+    }
+
+    #[test]
+    fn format_and_depth() {
+        let colorbuf = BitmapColorBuf::empty(ColorFormat::BGRA, BitDepth::Sixteen, 1, 1);
+
+        assert_eq!(&ColorFormat::BGRA, colorbuf.format());
+        assert_eq!(&BitDepth::Sixteen, colorbuf.depth());
+
+        let format = *colorbuf.format();
+        let depth = *colorbuf.depth();
+        let copy = BitmapColorBuf::empty(format, depth, 2, 2);
+        assert_eq!(&ColorFormat::BGRA, copy.format());
+        assert_eq!(&BitDepth::Sixteen, copy.depth());
+    }
+
+    #[test]
+    fn to_bitmap_stride() {
+        let mut stride = 0;
+        let mut output = [0x00u8; 40];
+        to_bitmap(
+            VecColorBuf::new(5, 2),
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            &mut stride,
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(20, stride);
+
+        to_bitmap(
+            VecColorBuf::new(5, 1),
+            ColorFormat::RGBA,
+            BitDepth::Sixteen,
+            &mut stride,
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(40, stride);
+
+        let colorbuf = BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            0,
+            u64::MAX,
+            0,
+            Box::new([]),
+        );
+        assert_eq!(
+            Err(BitmapError::ByteArrayTooSmall),
+            to_bitmap(
+                colorbuf,
+                ColorFormat::RGBA,
+                BitDepth::Sixteen,
+                &mut stride,
+                &mut output
+            )
+        );
+    }
+
+    #[test]
+    fn copy_format() {
+        let format = ColorFormat::GrayscaleAlpha;
+        let depth = BitDepth::Eight;
+        let colorbuf =
+            BitmapColorBuf::new(format, depth, 1, 2, 4, Box::new([0x00, 0xFF, 0xFF, 0x80]));
+        assert_eq!(ColorFormat::GrayscaleAlpha, *colorbuf.format());
+        assert_eq!(BitDepth::Eight, *colorbuf.depth());
+
+        let pixels = colorbuf.to_color_vec();
+        let mut output = [0; 8];
+        let mut stride = 0;
+        to_bitmap(colorbuf, format, depth, &mut stride, &mut output).unwrap();
+        assert_eq!([0x00, 0xFF, 0xFF, 0x80, 0x00, 0x00, 0x00, 0x00], output);
+
+        let decoded = from_bitmap(&output, format, depth, 1, 2, stride).unwrap();
+        assert_eq!(pixels, decoded.to_color_vec());
+    }
+
+    #[test]
+    fn fill_rect_matches_set_pixel() {
+        let color = Color {
+            r: 0.2f32,
+            g: 0.4f32,
+            b: 0.9f32,
+            a: 0.5f32,
+        };
+        let layouts = [
+            (ColorFormat::RGBA, BitDepth::Eight, false),
+            (ColorFormat::RGBA, BitDepth::Eight, true),
+            (ColorFormat::BGR, BitDepth::Sixteen, false),
+            (ColorFormat::GrayscaleAlpha, BitDepth::ThirtyTwoFloat, false),
+        ];
+        for (format, depth, premultiplied) in layouts.iter() {
+            let mut fast =
+                BitmapColorBuf::empty(*format, *depth, 5, 7).with_premultiplied(*premultiplied);
+            let mut slow =
+                BitmapColorBuf::empty(*format, *depth, 5, 7).with_premultiplied(*premultiplied);
+            fast.fill_rect(2, 1, 4, 3, &color).unwrap();
+            for y in 1..4 {
+                for x in 2..6 {
+                    slow.set_pixel(x, y, &color).unwrap();
+                }
+            }
+
+            assert_eq!(slow.as_bytes(), fast.as_bytes());
+        }
+    }
+
+    #[test]
+    fn fill_rect_past_edge() {
+        let mut colorbuf = BitmapColorBuf::empty(ColorFormat::RGB, BitDepth::Eight, 3, 3);
+
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            colorbuf.fill_rect(1, 1, 3, 1, &Color::WHITE)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            colorbuf.fill_rect(0, u64::MAX, 1, 2, &Color::WHITE)
+        );
+        assert_eq!(Ok(()), colorbuf.fill_rect(3, 3, 0, 0, &Color::WHITE));
+        assert!(colorbuf.as_bytes().iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn copy_from_bitmap() {
+        let data: Vec<u8> = (0..24).collect();
+        let src = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            2,
+            3,
+            12,
+            data.into_boxed_slice(),
+        );
+        let mut same = BitmapColorBuf::empty(ColorFormat::RGB, BitDepth::Eight, 2, 3);
+        same.copy_from_bitmap(&src).unwrap();
+
+        assert_eq!(src.row_bytes(0).unwrap(), same.row_bytes(0).unwrap());
+        assert_eq!(src.row_bytes(1).unwrap(), same.row_bytes(1).unwrap());
+
+        let mut other = BitmapColorBuf::empty(ColorFormat::BGRA, BitDepth::Sixteen, 2, 3);
+        other.copy_from_bitmap(&src).unwrap();
+        assert_eq!(src.to_color_vec(), other.to_color_vec());
+
+        let mut small = BitmapColorBuf::empty(ColorFormat::RGB, BitDepth::Eight, 1, 3);
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            small.copy_from_bitmap(&src)
+        );
+    }
 }
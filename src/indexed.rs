@@ -0,0 +1,224 @@
+//! [`ColorBuf`]s storing indices into a palette.
+//!
+//! # About
+//!
+//! This module gives a [`ColorBuf`] which stores every pixel as a single byte indexing into a
+//! palette of at most 256 [`Color`]s, like GIF images and 8-bit BMP images do.
+//!
+//! [`ColorBuf`]: ../trait.ColorBuf.html
+//! [`Color`]: ../struct.Color.html
+
+use crate::{Color, ColorBuf, ColorBufError};
+use std::convert::TryFrom;
+use std::result::Result;
+
+/// Tells the [`IndexedColorBuf`] how to pick a palette entry for a color being written.
+///
+/// [`IndexedColorBuf`]: struct.IndexedColorBuf.html
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteMatch {
+    /// The closest palette entry is used, as measured by [`Color::distance`].
+    ///
+    /// [`Color::distance`]: ../struct.Color.html#method.distance
+    Nearest,
+    /// Only a palette entry equal to the color is used. Other colors give
+    /// `ColorBufError::ColorNotInPalette`.
+    Exact,
+}
+
+/// [`ColorBuf`] storing its pixels as indices into a palette of [`Color`]s.
+///
+/// [`ColorBuf`]: ../trait.ColorBuf.html
+/// [`Color`]: ../struct.Color.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedColorBuf {
+    indices: Vec<u8>,
+    palette: Vec<Color>,
+    matching: PaletteMatch,
+
+    width: u64,
+    height: u64,
+}
+
+impl IndexedColorBuf {
+    /// Returns a new color buffer of the given size using `palette`.
+    ///
+    /// Every pixel starts out as the first entry of `palette`, and colors written to the buffer
+    /// are matched to the nearest palette entry. Returns `ColorBufError::InvalidPalette` if
+    /// `palette` is empty or has more than 256 entries, and `ColorBufError::InvalidDimensions` if
+    /// the amount of pixels doesn't fit into a `usize`.
+    pub fn new(
+        width: u64,
+        height: u64,
+        palette: Vec<Color>,
+    ) -> Result<IndexedColorBuf, ColorBufError> {
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(ColorBufError::InvalidPalette);
+        }
+        let len = width
+            .checked_mul(height)
+            .and_then(|len| usize::try_from(len).ok())
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        Ok(IndexedColorBuf {
+            indices: vec![0; len],
+            palette,
+            matching: PaletteMatch::Nearest,
+            width,
+            height,
+        })
+    }
+
+    /// Returns this buffer set to match written colors to the palette using `matching`.
+    pub fn with_matching(self, matching: PaletteMatch) -> IndexedColorBuf {
+        IndexedColorBuf { matching, ..self }
+    }
+
+    /// Returns the palette of this buffer.
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    /// Returns the palette index of every pixel, row by row.
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    fn get_offset(&self, x: u64, y: u64) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    /// Returns the index of the palette entry to store for `color`.
+    fn find_index(&self, color: &Color) -> Result<u8, ColorBufError> {
+        let index = match self.matching {
+            PaletteMatch::Nearest => self
+                .palette
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.distance(color).total_cmp(&b.distance(color)))
+                .map(|(index, _)| index),
+            PaletteMatch::Exact => self.palette.iter().position(|entry| entry == color),
+        };
+        index
+            .map(|index| index as u8)
+            .ok_or(ColorBufError::ColorNotInPalette)
+    }
+}
+
+impl ColorBuf for IndexedColorBuf {
+    fn get_pixel(&self, x: u64, y: u64) -> Result<Color, ColorBufError> {
+        if x >= self.width || y >= self.height {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        Ok(self.palette[self.indices[self.get_offset(x, y)] as usize])
+    }
+
+    fn set_pixel(&mut self, x: u64, y: u64, color: &Color) -> Result<(), ColorBufError> {
+        if x >= self.width || y >= self.height {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        let index = self.find_index(color)?;
+        let offset = self.get_offset(x, y);
+        self.indices[offset] = index;
+        Ok(())
+    }
+
+    fn get_width(&self) -> u64 {
+        self.width
+    }
+
+    fn get_height(&self) -> u64 {
+        self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette() -> Vec<Color> {
+        vec![Color::BLACK, Color::WHITE, Color::RED]
+    }
+
+    #[test]
+    fn read_through_palette() {
+        let mut colorbuf = IndexedColorBuf::new(2, 2, palette()).unwrap();
+        colorbuf.set_pixel(1, 0, &Color::RED).unwrap();
+        colorbuf.set_pixel(0, 1, &Color::WHITE).unwrap();
+
+        assert_eq!(&[0, 2, 1, 0], colorbuf.indices());
+        assert_eq!(Color::BLACK, colorbuf.get_pixel(0, 0).unwrap());
+        assert_eq!(Color::RED, colorbuf.get_pixel(1, 0).unwrap());
+        assert_eq!(Color::WHITE, colorbuf.get_pixel(0, 1).unwrap());
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            colorbuf.get_pixel(2, 0)
+        );
+    }
+
+    #[test]
+    fn write_nearest() {
+        let mut colorbuf = IndexedColorBuf::new(2, 1, palette()).unwrap();
+        colorbuf
+            .set_pixel(
+                0,
+                0,
+                &Color {
+                    r: 0.9f32,
+                    g: 0.2f32,
+                    b: 0.1f32,
+                    a: 1f32,
+                },
+            )
+            .unwrap();
+        colorbuf
+            .set_pixel(
+                1,
+                0,
+                &Color {
+                    r: 0.8f32,
+                    g: 0.7f32,
+                    b: 0.9f32,
+                    a: 1f32,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(Color::RED, colorbuf.get_pixel(0, 0).unwrap());
+        assert_eq!(Color::WHITE, colorbuf.get_pixel(1, 0).unwrap());
+    }
+
+    #[test]
+    fn write_exact() {
+        let mut colorbuf = IndexedColorBuf::new(1, 1, palette())
+            .unwrap()
+            .with_matching(PaletteMatch::Exact);
+
+        assert_eq!(Ok(()), colorbuf.set_pixel(0, 0, &Color::WHITE));
+        assert_eq!(
+            Err(ColorBufError::ColorNotInPalette),
+            colorbuf.set_pixel(0, 0, &Color::BLUE)
+        );
+        assert_eq!(Color::WHITE, colorbuf.get_pixel(0, 0).unwrap());
+    }
+
+    #[test]
+    fn invalid_palette() {
+        assert_eq!(
+            Err(ColorBufError::InvalidPalette),
+            IndexedColorBuf::new(1, 1, Vec::new())
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidPalette),
+            IndexedColorBuf::new(1, 1, vec![Color::BLACK; 257])
+        );
+        assert!(IndexedColorBuf::new(1, 1, vec![Color::BLACK; 256]).is_ok());
+    }
+
+    #[test]
+    fn overflowing_size() {
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            IndexedColorBuf::new(u64::MAX, 2, palette())
+        );
+    }
+}
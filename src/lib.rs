@@ -10,14 +10,50 @@
 //! [`ColorBuf`]: trait.ColorBuf.html
 //! [`Color`]: struct.Color.html
 
+use std::fmt;
 use std::result::Result;
 
 #[derive(Debug, PartialEq)]
 pub enum ColorBufError {
     InvalidCoordinate,
     InvalidDimensions,
+    /// The `ColorBuf` can't be written to.
+    ReadOnly,
+    /// The color isn't in the palette of the `ColorBuf`.
+    ColorNotInPalette,
+    /// The palette is empty or has too many entries.
+    InvalidPalette,
 }
 
+impl fmt::Display for ColorBufError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorBufError::InvalidCoordinate => write!(f, "pixel coordinate is out of bounds"),
+            ColorBufError::InvalidDimensions => write!(f, "dimensions are invalid"),
+            ColorBufError::ReadOnly => write!(f, "buffer is read-only"),
+            ColorBufError::ColorNotInPalette => write!(f, "color is not in the palette"),
+            ColorBufError::InvalidPalette => write!(f, "palette is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for ColorBufError {}
+
+#[derive(Debug, PartialEq)]
+pub enum ColorError {
+    InvalidHex,
+}
+
+impl fmt::Display for ColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorError::InvalidHex => write!(f, "invalid hexadecimal color"),
+        }
+    }
+}
+
+impl std::error::Error for ColorError {}
+
 /// 2D manipulatable region of pixels.
 pub trait ColorBuf {
     /// Gets the color at a given pixel position.
@@ -32,17 +68,208 @@ pub trait ColorBuf {
     /// `(0 <= x < width and 0 <= y < height)`.
     fn set_pixel(&mut self, x: u64, y: u64, color: &Color) -> Result<(), ColorBufError>;
 
+    /// Gets the color at a given pixel position without checking the bounds.
+    ///
+    /// The default implementation just calls [`get_pixel`] and panics on failure, but
+    /// implementations may skip the bounds checks altogether.
+    ///
+    /// # Safety
+    ///
+    /// The caller must make sure that `x < width` and `y < height`. Going outside of these bounds
+    /// is undefined behavior.
+    ///
+    /// [`get_pixel`]: #tymethod.get_pixel
+    unsafe fn get_pixel_unchecked(&self, x: u64, y: u64) -> Color {
+        self.get_pixel(x, y).unwrap()
+    }
+
+    /// Sets the color at a given pixel position without checking the bounds.
+    ///
+    /// The default implementation just calls [`set_pixel`] and panics on failure, but
+    /// implementations may skip the bounds checks altogether.
+    ///
+    /// # Safety
+    ///
+    /// The caller must make sure that `x < width` and `y < height`. Going outside of these bounds
+    /// is undefined behavior.
+    ///
+    /// [`set_pixel`]: #tymethod.set_pixel
+    unsafe fn set_pixel_unchecked(&mut self, x: u64, y: u64, color: &Color) {
+        self.set_pixel(x, y, color).unwrap()
+    }
+
     /// Gets the width of the `ColorBuf`.
     fn get_width(&self) -> u64;
 
     /// Gets the width of the `ColorBuf`.
     fn get_height(&self) -> u64;
+
+    /// Gets the width and height of the `ColorBuf`.
+    fn dimensions(&self) -> (u64, u64) {
+        (self.get_width(), self.get_height())
+    }
+
+    /// Checks whether the given pixel position is within the bounds of the `ColorBuf`.
+    fn in_bounds(&self, x: u64, y: u64) -> bool {
+        x < self.get_width() && y < self.get_height()
+    }
+
+    /// Gets the amount of pixels in the `ColorBuf`, or `None` if the amount doesn't fit into a
+    /// `u64`.
+    fn pixel_count(&self) -> Option<u64> {
+        self.get_width().checked_mul(self.get_height())
+    }
+
+    /// Reads every pixel of the `ColorBuf` into a `Vec`, row by row.
+    ///
+    /// The pixel at `(x, y)` ends up at index `y * width + x`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if reading any pixel within the bounds fails.
+    fn to_color_vec(&self) -> Vec<Color> {
+        let mut colors = Vec::with_capacity(self.pixel_count().unwrap_or(0) as usize);
+        for y in 0..self.get_height() {
+            colors.extend(self.get_row(y).unwrap());
+        }
+        colors
+    }
+
+    /// Gets the colors of every pixel on row `y`, from left to right.
+    ///
+    /// Buffers that can decode a whole row faster than pixel by pixel should override this.
+    fn get_row(&self, y: u64) -> Result<Vec<Color>, ColorBufError> {
+        if y >= self.get_height() {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        (0..self.get_width())
+            .map(|x| self.get_pixel(x, y))
+            .collect()
+    }
+
+    /// Gets the pixel at the normalized coordinates `(u, v)`.
+    ///
+    /// `(0, 0)` is the top-left corner and `(1, 1)` the bottom-right corner of the `ColorBuf`.
+    /// The coordinates are clamped to `[0, 1]` and mapped to the pixel containing them. An empty
+    /// `ColorBuf` gives `ColorBufError::InvalidCoordinate`.
+    fn sample_uv(&self, u: f32, v: f32) -> Result<Color, ColorBufError> {
+        let to_pixel = |t: f32, len: u64| {
+            ((t.clamp(0f32, 1f32) * len as f32) as u64).min(len.saturating_sub(1))
+        };
+        self.get_pixel(
+            to_pixel(u, self.get_width()),
+            to_pixel(v, self.get_height()),
+        )
+    }
+
+    /// Gets the color at the normalized coordinates `(u, v)` by bilinearly interpolating the four
+    /// pixels around it.
+    ///
+    /// Works like [`sample_uv`], except that the pixel centers are taken as the sample points and
+    /// the colors between them are blended with [`Color::lerp`]. Samples past the outermost pixel
+    /// centers are clamped to the edge.
+    ///
+    /// [`sample_uv`]: #method.sample_uv
+    /// [`Color::lerp`]: struct.Color.html#method.lerp
+    fn sample_uv_bilinear(&self, u: f32, v: f32) -> Result<Color, ColorBufError> {
+        let (width, height) = self.dimensions();
+        if width == 0 || height == 0 {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        let sample_points = |t: f32, len: u64| {
+            let pos = (t.clamp(0f32, 1f32) * len as f32 - 0.5f32).clamp(0f32, (len - 1) as f32);
+            let first = pos.floor() as u64;
+            (first, (first + 1).min(len - 1), pos - first as f32)
+        };
+        let (x0, x1, tx) = sample_points(u, width);
+        let (y0, y1, ty) = sample_points(v, height);
+
+        let top = self.get_pixel(x0, y0)?.lerp(self.get_pixel(x1, y0)?, tx);
+        let bottom = self.get_pixel(x0, y1)?.lerp(self.get_pixel(x1, y1)?, tx);
+        Ok(top.lerp(bottom, ty))
+    }
+
+    /// Copies every pixel of `src` into this `ColorBuf`.
+    ///
+    /// Both buffers must have the same dimensions, otherwise `ColorBufError::InvalidDimensions`
+    /// is returned.
+    fn copy_from<S: ColorBuf>(&mut self, src: &S) -> Result<(), ColorBufError>
+    where
+        Self: Sized,
+    {
+        if src.get_width() != self.get_width() || src.get_height() != self.get_height() {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        for y in 0..self.get_height() {
+            for x in 0..self.get_width() {
+                self.set_pixel(x, y, &src.get_pixel(x, y)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets every pixel of the `ColorBuf` to `color`.
+    fn fill(&mut self, color: &Color) -> Result<(), ColorBufError> {
+        for y in 0..self.get_height() {
+            for x in 0..self.get_width() {
+                self.set_pixel(x, y, color)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets every pixel in a rectangle of the `ColorBuf` to `color`.
+    ///
+    /// The rectangle starts at `(x, y)` and is `width` pixels wide and `height` pixels high. It
+    /// must lie fully inside the `ColorBuf`, otherwise `ColorBufError::InvalidDimensions` is
+    /// returned and nothing is written.
+    fn fill_rect(
+        &mut self,
+        x: u64,
+        y: u64,
+        width: u64,
+        height: u64,
+        color: &Color,
+    ) -> Result<(), ColorBufError> {
+        let end_x = x
+            .checked_add(width)
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        let end_y = y
+            .checked_add(height)
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        if end_x > self.get_width() || end_y > self.get_height() {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        for y in y..end_y {
+            for x in x..end_x {
+                self.set_pixel(x, y, color)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A [`ColorBuf`] whose rows can be modified from different threads at once.
+///
+/// Only available with the `rayon` feature.
+///
+/// [`ColorBuf`]: trait.ColorBuf.html
+#[cfg(feature = "rayon")]
+pub trait ParRowsMut: ColorBuf {
+    /// Returns a parallel iterator over the rows of this buffer for modification.
+    ///
+    /// Every item holds the colors of one row from left to right, and the rows don't overlap.
+    fn par_rows_mut(&mut self) -> impl rayon::iter::IndexedParallelIterator<Item = &mut [Color]>;
 }
 
 /// Color of a pixel.
 ///
 /// This struct represents a single straigh alpha RGBA color value.
+///
+/// With the `serde` feature enabled, colors are serialized as objects with the fields `r`, `g`,
+/// `b` and `a`.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     /// The red color channel. Values range from 0 to 1.
     pub r: f32,
@@ -55,6 +282,401 @@ pub struct Color {
 }
 
 impl Color {
+    /// Opaque black.
+    pub const BLACK: Color = Color {
+        r: 0f32,
+        g: 0f32,
+        b: 0f32,
+        a: 1f32,
+    };
+    /// Opaque white.
+    pub const WHITE: Color = Color {
+        r: 1f32,
+        g: 1f32,
+        b: 1f32,
+        a: 1f32,
+    };
+    /// Opaque red.
+    pub const RED: Color = Color {
+        r: 1f32,
+        g: 0f32,
+        b: 0f32,
+        a: 1f32,
+    };
+    /// Opaque green.
+    pub const GREEN: Color = Color {
+        r: 0f32,
+        g: 1f32,
+        b: 0f32,
+        a: 1f32,
+    };
+    /// Opaque blue.
+    pub const BLUE: Color = Color {
+        r: 0f32,
+        g: 0f32,
+        b: 1f32,
+        a: 1f32,
+    };
+    /// Fully transparent black.
+    pub const TRANSPARENT: Color = Color {
+        r: 0f32,
+        g: 0f32,
+        b: 0f32,
+        a: 0f32,
+    };
+
+    /// Returns a new color from 8-bit channel values.
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color {
+            r: r as f32 / 255f32,
+            g: g as f32 / 255f32,
+            b: b as f32 / 255f32,
+            a: a as f32 / 255f32,
+        }
+    }
+
+    /// Returns the channels of this color as 8-bit values.
+    ///
+    /// The channels are rounded to the nearest value, and values outside of `[0, 1]` are
+    /// saturated.
+    pub fn to_rgba8(&self) -> (u8, u8, u8, u8) {
+        let to_byte = |channel: f32| (channel * 255f32 + 0.5f32).clamp(0f32, 255f32) as u8;
+
+        (
+            to_byte(self.r),
+            to_byte(self.g),
+            to_byte(self.b),
+            to_byte(self.a),
+        )
+    }
+
+    /// Parses a color from a hexadecimal string.
+    ///
+    /// The string can be in the forms `RGB`, `RGBA`, `RRGGBB` or `RRGGBBAA`, optionally prefixed
+    /// with `#`. When the alpha is not given, the color is opaque.
+    pub fn from_hex(s: &str) -> Result<Color, ColorError> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(ColorError::InvalidHex);
+        }
+
+        let parse = |digits: &str| u8::from_str_radix(digits, 16).unwrap();
+        let (r, g, b, a) = match digits.len() {
+            3 | 4 => {
+                // Every digit is repeated, so that e.g. `F` becomes `FF`.
+                let channel = |n: usize| parse(&digits[n..n + 1]) * 17;
+                let a = if digits.len() == 4 { channel(3) } else { 255 };
+                (channel(0), channel(1), channel(2), a)
+            }
+            6 | 8 => {
+                let channel = |n: usize| parse(&digits[2 * n..2 * n + 2]);
+                let a = if digits.len() == 8 { channel(3) } else { 255 };
+                (channel(0), channel(1), channel(2), a)
+            }
+            _ => return Err(ColorError::InvalidHex),
+        };
+
+        Ok(Color::from_rgba8(r, g, b, a))
+    }
+
+    /// Formats this color as a `#RRGGBBAA` hexadecimal string.
+    ///
+    /// The channels are rounded like in [`to_rgba8`].
+    ///
+    /// [`to_rgba8`]: #method.to_rgba8
+    pub fn to_hex(&self) -> String {
+        let (r, g, b, a) = self.to_rgba8();
+        format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a)
+    }
+
+    /// Formats this color as a `#RRGGBB` hexadecimal string, dropping the alpha.
+    ///
+    /// The channels are rounded like in [`to_rgba8`].
+    ///
+    /// [`to_rgba8`]: #method.to_rgba8
+    pub fn to_hex_rgb(&self) -> String {
+        let (r, g, b, _) = self.to_rgba8();
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    }
+
+    /// Returns this color with the alpha channel replaced by `a`, clamped to `[0, 1]`.
+    pub fn with_alpha(self, a: f32) -> Color {
+        Color {
+            a: a.clamp(0f32, 1f32),
+            ..self
+        }
+    }
+
+    /// Returns the value of the given channel of this color.
+    pub(crate) fn channel(&self, channel: Channel) -> f32 {
+        match channel {
+            Channel::R => self.r,
+            Channel::G => self.g,
+            Channel::B => self.b,
+            Channel::A => self.a,
+        }
+    }
+
+    /// Returns a new color whose red, green, blue and alpha channels are picked from this color
+    /// in the given `order`.
+    ///
+    /// For example, `[Channel::B, Channel::G, Channel::R, Channel::A]` swaps red and blue.
+    pub fn swizzle(self, order: [Channel; 4]) -> Color {
+        Color {
+            r: self.channel(order[0]),
+            g: self.channel(order[1]),
+            b: self.channel(order[2]),
+            a: self.channel(order[3]),
+        }
+    }
+
+    /// Returns this color with every color channel inverted. Alpha is left as is.
+    pub fn inverted(&self) -> Color {
+        Color {
+            r: 1f32 - self.r,
+            g: 1f32 - self.g,
+            b: 1f32 - self.b,
+            a: self.a,
+        }
+    }
+
+    /// Returns this color with every channel saturated to the range `[0, 1]`.
+    pub fn clamped(&self) -> Color {
+        Color {
+            r: self.r.clamp(0f32, 1f32),
+            g: self.g.clamp(0f32, 1f32),
+            b: self.b.clamp(0f32, 1f32),
+            a: self.a.clamp(0f32, 1f32),
+        }
+    }
+
+    /// Returns this color with the color channels multiplied by the alpha channel.
+    pub fn premultiplied(&self) -> Color {
+        Color {
+            r: self.r * self.a,
+            g: self.g * self.a,
+            b: self.b * self.a,
+            a: self.a,
+        }
+    }
+
+    /// Returns this premultiplied color with the color channels divided by the alpha channel.
+    ///
+    /// A fully transparent color becomes transparent black.
+    pub fn unpremultiplied(&self) -> Color {
+        if self.a == 0f32 {
+            return Color::TRANSPARENT;
+        }
+        Color {
+            r: self.r / self.a,
+            g: self.g / self.a,
+            b: self.b / self.a,
+            a: self.a,
+        }
+    }
+
+    /// Returns the luminance of this color in every color channel.
+    ///
+    /// The luminance is computed using the Rec. 709 weights. Alpha is left as is.
+    pub fn to_grayscale(&self) -> Color {
+        let luminance = 0.2126f32 * self.r + 0.7152f32 * self.g + 0.0722f32 * self.b;
+
+        Color {
+            r: luminance,
+            g: luminance,
+            b: luminance,
+            a: self.a,
+        }
+    }
+
+    /// Returns the hue, saturation and value of this color.
+    ///
+    /// The hue is in degrees within `[0, 360)`, while the saturation and value are within
+    /// `[0, 1]`. The hue of achromatic colors is `0`.
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let delta = max - min;
+
+        let saturation = if max == 0f32 { 0f32 } else { delta / max };
+
+        (hue(self.r, self.g, self.b, max, delta), saturation, max)
+    }
+
+    /// Returns a new color from the given hue, saturation, value and alpha.
+    ///
+    /// The hue is in degrees and wraps around `360`.
+    pub fn from_hsv(h: f32, s: f32, v: f32, a: f32) -> Color {
+        let chroma = v * s;
+        let (r, g, b) = from_hue(h, chroma);
+        let m = v - chroma;
+
+        Color {
+            r: r + m,
+            g: g + m,
+            b: b + m,
+            a,
+        }
+    }
+
+    /// Returns the hue, saturation and lightness of this color.
+    ///
+    /// The hue is in degrees within `[0, 360)`, while the saturation and lightness are within
+    /// `[0, 1]`. The hue and saturation of achromatic colors are `0`.
+    pub fn to_hsl(&self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let delta = max - min;
+
+        let lightness = (max + min) / 2f32;
+        let saturation = if delta == 0f32 {
+            0f32
+        } else {
+            delta / (1f32 - (2f32 * lightness - 1f32).abs())
+        };
+
+        (
+            hue(self.r, self.g, self.b, max, delta),
+            saturation,
+            lightness,
+        )
+    }
+
+    /// Returns a new color from the given hue, saturation, lightness and alpha.
+    ///
+    /// The hue is in degrees and wraps around `360`.
+    pub fn from_hsl(h: f32, s: f32, l: f32, a: f32) -> Color {
+        let chroma = (1f32 - (2f32 * l - 1f32).abs()) * s;
+        let (r, g, b) = from_hue(h, chroma);
+        let m = l - chroma / 2f32;
+
+        Color {
+            r: r + m,
+            g: g + m,
+            b: b + m,
+            a,
+        }
+    }
+
+    /// Returns this color with every color channel raised to the power of `1 / gamma`.
+    ///
+    /// This encodes a linear color with the given `gamma`. Decoding is done by passing in
+    /// `1 / gamma` instead. Alpha is left as is.
+    pub fn with_gamma(self, gamma: f32) -> Color {
+        Color {
+            r: self.r.powf(1f32 / gamma),
+            g: self.g.powf(1f32 / gamma),
+            b: self.b.powf(1f32 / gamma),
+            a: self.a,
+        }
+    }
+
+    /// Converts this color from sRGB into linear light.
+    ///
+    /// This uses the piecewise transfer function of IEC 61966-2-1. Alpha is left as is.
+    pub fn srgb_to_linear(&self) -> Color {
+        let decode = |channel: f32| {
+            if channel <= 0.04045f32 {
+                channel / 12.92f32
+            } else {
+                ((channel + 0.055f32) / 1.055f32).powf(2.4f32)
+            }
+        };
+
+        Color {
+            r: decode(self.r),
+            g: decode(self.g),
+            b: decode(self.b),
+            a: self.a,
+        }
+    }
+
+    /// Converts this color from linear light into sRGB.
+    ///
+    /// This uses the piecewise transfer function of IEC 61966-2-1. Alpha is left as is.
+    pub fn linear_to_srgb(&self) -> Color {
+        let encode = |channel: f32| {
+            if channel <= 0.0031308f32 {
+                channel * 12.92f32
+            } else {
+                1.055f32 * channel.powf(1f32 / 2.4f32) - 0.055f32
+            }
+        };
+
+        Color {
+            r: encode(self.r),
+            g: encode(self.g),
+            b: encode(self.b),
+            a: self.a,
+        }
+    }
+
+    /// Linearly interpolates every channel, including alpha, from this color to `other`.
+    ///
+    /// The colors are interpolated as straight alpha. `t` is clamped to `[0, 1]`, where `0` gives
+    /// this color and `1` gives `other`.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0f32, 1f32);
+
+        Color {
+            r: self.r + (other.r - self.r) * t,
+            g: self.g + (other.g - self.g) * t,
+            b: self.b + (other.b - self.b) * t,
+            a: self.a + (other.a - self.a) * t,
+        }
+    }
+
+    /// Returns the Euclidean distance between the RGBA channels of this color and `other`.
+    pub fn distance(&self, other: &Color) -> f32 {
+        let dr = self.r - other.r;
+        let dg = self.g - other.g;
+        let db = self.b - other.b;
+        let da = self.a - other.a;
+        (dr * dr + dg * dg + db * db + da * da).sqrt()
+    }
+
+    /// Returns the Euclidean distance between the color channels of this color and `other`,
+    /// ignoring alpha.
+    pub fn distance_rgb(&self, other: &Color) -> f32 {
+        let dr = self.r - other.r;
+        let dg = self.g - other.g;
+        let db = self.b - other.b;
+        (dr * dr + dg * dg + db * db).sqrt()
+    }
+
+    /// Checks whether every channel of this color differs from `other` by at most `epsilon`.
+    ///
+    /// This is usually a better fit than `==` for colors that went through floating point math.
+    pub fn approx_eq(&self, other: &Color, epsilon: f32) -> bool {
+        (self.r - other.r).abs() <= epsilon
+            && (self.g - other.g).abs() <= epsilon
+            && (self.b - other.b).abs() <= epsilon
+            && (self.a - other.a).abs() <= epsilon
+    }
+
+    /// Blends `src` to this color using the straight alpha "over" operator.
+    ///
+    /// Unlike [`blend_with_gamma`], the channels are mixed linearly.
+    ///
+    /// [`blend_with_gamma`]: #method.blend_with_gamma
+    pub fn blend(self, src: Color) -> Color {
+        let out_a = src.a + self.a * (1f32 - src.a);
+        if out_a == 0f32 {
+            return Color::TRANSPARENT;
+        }
+        let dst_weight = self.a * (1f32 - src.a);
+        let out_r = (src.r * src.a + self.r * dst_weight) / out_a;
+        let out_g = (src.g * src.a + self.g * dst_weight) / out_a;
+        let out_b = (src.b * src.a + self.b * dst_weight) / out_a;
+
+        Color {
+            r: out_r,
+            g: out_g,
+            b: out_b,
+            a: out_a,
+        }
+    }
+
     /// Blends `src` to this color with gamma correcion.
     ///
     /// NOTE: `gamma` is usually `2.2f32`.
@@ -76,5 +698,554 @@ impl Color {
     }
 }
 
+/// A single channel of a [`Color`].
+///
+/// [`Color`]: struct.Color.html
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    A,
+}
+
+/// The default color is transparent black, which matches a zero-initialized buffer.
+impl Default for Color {
+    fn default() -> Color {
+        Color::TRANSPARENT
+    }
+}
+
+/// Computes the hue in degrees of the given color channels.
+fn hue(r: f32, g: f32, b: f32, max: f32, delta: f32) -> f32 {
+    if delta == 0f32 {
+        return 0f32;
+    }
+
+    let hue = if max == r {
+        60f32 * ((g - b) / delta)
+    } else if max == g {
+        60f32 * ((b - r) / delta + 2f32)
+    } else {
+        60f32 * ((r - g) / delta + 4f32)
+    };
+
+    if hue < 0f32 {
+        hue + 360f32
+    } else {
+        hue
+    }
+}
+
+/// Computes the color channels of the given hue in degrees and chroma, without any lightness.
+fn from_hue(h: f32, chroma: f32) -> (f32, f32, f32) {
+    let h = h.rem_euclid(360f32) / 60f32;
+    let x = chroma * (1f32 - (h % 2f32 - 1f32).abs());
+
+    if h < 1f32 {
+        (chroma, x, 0f32)
+    } else if h < 2f32 {
+        (x, chroma, 0f32)
+    } else if h < 3f32 {
+        (0f32, chroma, x)
+    } else if h < 4f32 {
+        (0f32, x, chroma)
+    } else if h < 5f32 {
+        (x, 0f32, chroma)
+    } else {
+        (chroma, 0f32, x)
+    }
+}
+
 pub mod bitmap;
+pub mod indexed;
 pub mod ops;
+pub mod vec;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamped() {
+        let color = Color {
+            r: -0.5f32,
+            g: 0f32,
+            b: 1f32,
+            a: 1.5f32,
+        };
+
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 1f32,
+                a: 1f32,
+            },
+            color.clamped()
+        );
+    }
+
+    #[test]
+    fn blend_opaque() {
+        let dst = Color {
+            r: 1f32,
+            g: 1f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let src = Color {
+            r: 0f32,
+            g: 0.5f32,
+            b: 1f32,
+            a: 1f32,
+        };
+
+        assert_eq!(src, dst.blend(src));
+    }
+
+    #[test]
+    fn blend_semi_transparent() {
+        let dst = Color {
+            r: 0f32,
+            g: 0f32,
+            b: 1f32,
+            a: 0.5f32,
+        };
+        let src = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 0.5f32,
+        };
+        let blended = dst.blend(src);
+
+        // out_a = 0.5 + 0.5 * 0.5 = 0.75
+        // out_r = (1 * 0.5) / 0.75, out_b = (1 * 0.25) / 0.75
+        assert!((blended.a - 0.75f32).abs() < 1e-6);
+        assert!((blended.r - 2f32 / 3f32).abs() < 1e-6);
+        assert!((blended.g - 0f32).abs() < 1e-6);
+        assert!((blended.b - 1f32 / 3f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn blend_transparent() {
+        let transparent = Color {
+            r: 1f32,
+            g: 1f32,
+            b: 1f32,
+            a: 0f32,
+        };
+
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0f32,
+            },
+            transparent.blend(transparent)
+        );
+    }
+
+    #[test]
+    fn premultiplied_roundtrip() {
+        let color = Color {
+            r: 0.8f32,
+            g: 0.4f32,
+            b: 0.1f32,
+            a: 0.3f32,
+        };
+        let premultiplied = color.premultiplied();
+
+        assert!((premultiplied.r - 0.24f32).abs() < 1e-6);
+        assert!((premultiplied.a - 0.3f32).abs() < 1e-6);
+
+        let roundtripped = premultiplied.unpremultiplied();
+        assert!((roundtripped.r - color.r).abs() < 1e-6);
+        assert!((roundtripped.g - color.g).abs() < 1e-6);
+        assert!((roundtripped.b - color.b).abs() < 1e-6);
+        assert!((roundtripped.a - color.a).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unpremultiplied_transparent() {
+        let color = Color {
+            r: 0.5f32,
+            g: 0.5f32,
+            b: 0.5f32,
+            a: 0f32,
+        };
+
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0f32,
+            },
+            color.unpremultiplied()
+        );
+    }
+
+    #[test]
+    fn to_grayscale() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 0.5f32,
+        };
+
+        assert_eq!(
+            Color {
+                r: 0.2126f32,
+                g: 0.2126f32,
+                b: 0.2126f32,
+                a: 0.5f32,
+            },
+            red.to_grayscale()
+        );
+    }
+
+    #[test]
+    fn hsv_primaries() {
+        let primaries = [
+            (1f32, 0f32, 0f32, 0f32),
+            (0f32, 1f32, 0f32, 120f32),
+            (0f32, 0f32, 1f32, 240f32),
+        ];
+
+        for &(r, g, b, hue) in primaries.iter() {
+            let color = Color { r, g, b, a: 0.5f32 };
+
+            assert_eq!((hue, 1f32, 1f32), color.to_hsv());
+            assert_eq!(color, Color::from_hsv(hue, 1f32, 1f32, 0.5f32));
+        }
+    }
+
+    #[test]
+    fn hsv_gray() {
+        let gray = Color {
+            r: 0.5f32,
+            g: 0.5f32,
+            b: 0.5f32,
+            a: 1f32,
+        };
+
+        assert_eq!((0f32, 0f32, 0.5f32), gray.to_hsv());
+        assert_eq!(gray, Color::from_hsv(0f32, 0f32, 0.5f32, 1f32));
+    }
+
+    #[test]
+    fn hsl_roundtrip() {
+        let colors = [
+            (0f32, 0f32, 0f32, (0f32, 0f32, 0f32)),
+            (1f32, 1f32, 1f32, (0f32, 0f32, 1f32)),
+            (0.5f32, 0.5f32, 0.5f32, (0f32, 0f32, 0.5f32)),
+            (0f32, 0f32, 1f32, (240f32, 1f32, 0.5f32)),
+        ];
+
+        for &(r, g, b, hsl) in colors.iter() {
+            let color = Color { r, g, b, a: 1f32 };
+            assert_eq!(hsl, color.to_hsl());
+
+            let (h, s, l) = hsl;
+            let roundtripped = Color::from_hsl(h, s, l, 1f32);
+            assert!((roundtripped.r - r).abs() < f32::EPSILON);
+            assert!((roundtripped.g - g).abs() < f32::EPSILON);
+            assert!((roundtripped.b - b).abs() < f32::EPSILON);
+            assert_eq!(1f32, roundtripped.a);
+        }
+    }
+
+    #[test]
+    fn with_gamma_inverse() {
+        let color = Color {
+            r: 0.1f32,
+            g: 0.5f32,
+            b: 0.9f32,
+            a: 0.4f32,
+        };
+        let encoded = color.with_gamma(2.2f32);
+
+        assert!(encoded.r > color.r);
+        assert_eq!(color.a, encoded.a);
+
+        let decoded = encoded.with_gamma(1f32 / 2.2f32);
+        assert!((decoded.r - color.r).abs() < 1e-6);
+        assert!((decoded.g - color.g).abs() < 1e-6);
+        assert!((decoded.b - color.b).abs() < 1e-6);
+        assert_eq!(color.a, decoded.a);
+    }
+
+    #[test]
+    fn srgb_linear() {
+        let srgb = Color {
+            r: 0.5f32,
+            g: 0.02f32,
+            b: 1f32,
+            a: 0.5f32,
+        };
+        let linear = srgb.srgb_to_linear();
+
+        assert!((linear.r - 0.214_041_14f32).abs() < 1e-6);
+        assert!((linear.g - 0.02f32 / 12.92f32).abs() < 1e-6);
+        assert!((linear.b - 1f32).abs() < 1e-6);
+        assert_eq!(0.5f32, linear.a);
+
+        let roundtripped = linear.linear_to_srgb();
+        assert!((roundtripped.r - srgb.r).abs() < 1e-6);
+        assert!((roundtripped.g - srgb.g).abs() < 1e-6);
+        assert!((roundtripped.b - srgb.b).abs() < 1e-6);
+        assert_eq!(0.5f32, roundtripped.a);
+    }
+
+    #[test]
+    fn lerp() {
+        let black = Color {
+            r: 0f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let white = Color {
+            r: 1f32,
+            g: 1f32,
+            b: 1f32,
+            a: 0f32,
+        };
+
+        assert_eq!(black, black.lerp(white, 0f32));
+        assert_eq!(white, black.lerp(white, 1f32));
+        assert_eq!(
+            Color {
+                r: 0.5f32,
+                g: 0.5f32,
+                b: 0.5f32,
+                a: 0.5f32,
+            },
+            black.lerp(white, 0.5f32)
+        );
+        assert_eq!(white, black.lerp(white, 2f32));
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!((0f32, 0f32, 0f32, 1f32), rgba(Color::BLACK));
+        assert_eq!((1f32, 1f32, 1f32, 1f32), rgba(Color::WHITE));
+        assert_eq!((1f32, 0f32, 0f32, 1f32), rgba(Color::RED));
+        assert_eq!((0f32, 1f32, 0f32, 1f32), rgba(Color::GREEN));
+        assert_eq!((0f32, 0f32, 1f32, 1f32), rgba(Color::BLUE));
+        assert_eq!((0f32, 0f32, 0f32, 0f32), rgba(Color::TRANSPARENT));
+    }
+
+    fn rgba(color: Color) -> (f32, f32, f32, f32) {
+        (color.r, color.g, color.b, color.a)
+    }
+
+    #[test]
+    fn rgba8() {
+        assert_eq!(Color::RED, Color::from_rgba8(255, 0, 0, 255));
+        assert_eq!((255, 0, 0, 255), Color::RED.to_rgba8());
+
+        let color = Color::from_rgba8(128, 1, 254, 128);
+        assert_eq!((128, 1, 254, 128), color.to_rgba8());
+
+        let out_of_range = Color {
+            r: -0.5f32,
+            g: 0.5f32,
+            b: 1.5f32,
+            a: 1f32,
+        };
+        assert_eq!((0, 128, 255, 255), out_of_range.to_rgba8());
+    }
+
+    #[test]
+    fn from_hex() {
+        assert_eq!(Ok(Color::RED), Color::from_hex("#F00"));
+        assert_eq!(
+            Ok(Color::from_rgba8(0x11, 0x22, 0x33, 0x44)),
+            Color::from_hex("1234")
+        );
+        assert_eq!(
+            Ok(Color::from_rgba8(0xAB, 0xCD, 0xEF, 0xFF)),
+            Color::from_hex("#abcdef")
+        );
+        assert_eq!(
+            Ok(Color::from_rgba8(0xAB, 0xCD, 0xEF, 0x80)),
+            Color::from_hex("ABcdEF80")
+        );
+    }
+
+    #[test]
+    fn from_invalid_hex() {
+        assert_eq!(Err(ColorError::InvalidHex), Color::from_hex(""));
+        assert_eq!(Err(ColorError::InvalidHex), Color::from_hex("#"));
+        assert_eq!(Err(ColorError::InvalidHex), Color::from_hex("#12345"));
+        assert_eq!(Err(ColorError::InvalidHex), Color::from_hex("#12345G"));
+        assert_eq!(Err(ColorError::InvalidHex), Color::from_hex("##123"));
+        assert_eq!(Err(ColorError::InvalidHex), Color::from_hex("+1+2+3"));
+    }
+
+    #[test]
+    fn to_hex() {
+        for hex in ["#00000000", "#FF0000FF", "#12345678", "#ABCDEF80"].iter() {
+            assert_eq!(*hex, Color::from_hex(hex).unwrap().to_hex());
+        }
+        for hex in ["#000000", "#FFFFFF", "#0A0B0C"].iter() {
+            assert_eq!(*hex, Color::from_hex(hex).unwrap().to_hex_rgb());
+        }
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0f32,
+            },
+            Color::default()
+        );
+    }
+
+    #[test]
+    fn inverted() {
+        let gray = Color {
+            r: 0.5f32,
+            g: 0.5f32,
+            b: 0.5f32,
+            a: 0.25f32,
+        };
+
+        assert_eq!(Color::BLACK, Color::WHITE.inverted());
+        assert_eq!(gray, gray.inverted());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let color = Color {
+            r: 0.25f32,
+            g: 0.5f32,
+            b: 0.75f32,
+            a: 1f32,
+        };
+        let json = serde_json::to_string(&color).unwrap();
+
+        assert_eq!(r#"{"r":0.25,"g":0.5,"b":0.75,"a":1.0}"#, json);
+        assert_eq!(color, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let errors: Vec<Box<dyn std::error::Error>> = vec![
+            Box::new(ColorBufError::InvalidCoordinate),
+            Box::new(ColorBufError::InvalidDimensions),
+            Box::new(ColorBufError::ReadOnly),
+            Box::new(ColorBufError::ColorNotInPalette),
+            Box::new(ColorBufError::InvalidPalette),
+            Box::new(ColorError::InvalidHex),
+        ];
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(
+            vec![
+                "pixel coordinate is out of bounds",
+                "dimensions are invalid",
+                "buffer is read-only",
+                "color is not in the palette",
+                "palette is invalid",
+                "invalid hexadecimal color",
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn with_alpha() {
+        let color = Color {
+            r: 0.1f32,
+            g: 0.2f32,
+            b: 0.3f32,
+            a: 1f32,
+        };
+
+        assert_eq!(
+            Color {
+                r: 0.1f32,
+                g: 0.2f32,
+                b: 0.3f32,
+                a: 0.5f32,
+            },
+            color.with_alpha(0.5f32)
+        );
+        assert_eq!(0f32, color.with_alpha(-1f32).a);
+    }
+
+    #[test]
+    fn swizzle() {
+        let color = Color {
+            r: 0.1f32,
+            g: 0.2f32,
+            b: 0.3f32,
+            a: 0.4f32,
+        };
+
+        assert_eq!(
+            Color {
+                r: 0.3f32,
+                g: 0.2f32,
+                b: 0.1f32,
+                a: 0.4f32,
+            },
+            color.swizzle([Channel::B, Channel::G, Channel::R, Channel::A])
+        );
+        assert_eq!(
+            Color {
+                r: 0.4f32,
+                g: 0.4f32,
+                b: 0.1f32,
+                a: 0.1f32,
+            },
+            color.swizzle([Channel::A, Channel::A, Channel::R, Channel::R])
+        );
+    }
+
+    #[test]
+    fn distance() {
+        let color = Color {
+            r: 0.1f32,
+            g: 0.2f32,
+            b: 0.3f32,
+            a: 0.4f32,
+        };
+
+        assert_eq!(0f32, color.distance(&color));
+        assert_eq!(0f32, color.distance_rgb(&color));
+        assert_eq!(3f32.sqrt(), Color::BLACK.distance_rgb(&Color::WHITE));
+        assert_eq!(3f32.sqrt(), Color::BLACK.distance(&Color::WHITE));
+        assert_eq!(1f32, Color::BLACK.distance(&Color::TRANSPARENT));
+        assert_eq!(0f32, Color::BLACK.distance_rgb(&Color::TRANSPARENT));
+    }
+
+    #[test]
+    fn approx_eq() {
+        let color = Color {
+            r: 0.1f32,
+            g: 0.2f32,
+            b: 0.3f32,
+            a: 0.4f32,
+        };
+        let close = Color {
+            r: 0.1f32 + 1e-7f32,
+            ..color
+        };
+        let far = Color { a: 0.5f32, ..color };
+
+        assert!(color.approx_eq(&close, 1e-6f32));
+        assert!(!color.approx_eq(&far, 1e-6f32));
+    }
+}
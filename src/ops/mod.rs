@@ -7,6 +7,10 @@
 //! [`ColorBuf`]: ../struct.ColorBuf.html
 
 use super::*;
+use crate::bitmap::{BitDepth, BitmapColorBuf, ColorFormat};
+use crate::vec::VecColorBuf;
+use std::collections::HashSet;
+use std::convert::TryFrom;
 
 type Result<T> = std::result::Result<T, ColorBufError>;
 
@@ -38,7 +42,13 @@ where
         width: u64,
         height: u64,
     ) -> Result<SubRegionColorBuf<'a, B>> {
-        if (start_x + width) >= backing.get_width() || (start_y + height) >= backing.get_height() {
+        let end_x = start_x
+            .checked_add(width)
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        let end_y = start_y
+            .checked_add(height)
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        if end_x > backing.get_width() || end_y > backing.get_height() {
             return Err(ColorBufError::InvalidDimensions);
         }
         Ok(SubRegionColorBuf {
@@ -49,6 +59,64 @@ where
             height,
         })
     }
+
+    /// Returns a new subregion like [`new`], but shrinks `width` and `height` so that the region
+    /// fits inside `backing` instead of failing.
+    ///
+    /// A region starting past the right or bottom edge of `backing` ends up with a width or height
+    /// of `0`.
+    ///
+    /// [`new`]: #method.new
+    pub fn new_clamped(
+        backing: &'a mut B,
+        start_x: u64,
+        start_y: u64,
+        width: u64,
+        height: u64,
+    ) -> SubRegionColorBuf<'a, B> {
+        let width = width.min(backing.get_width().saturating_sub(start_x));
+        let height = height.min(backing.get_height().saturating_sub(start_y));
+        SubRegionColorBuf {
+            backing,
+            reg_x: start_x,
+            reg_y: start_y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns a region of this subregion, with the coordinates relative to this subregion.
+    ///
+    /// Instead of wrapping `self`, the returned subregion points straight at the original backing
+    /// [`ColorBuf`] with the offsets of both regions added together, so nesting regions does not
+    /// add any indirection. The returned subregion borrows `self` mutably, so this subregion can
+    /// not be used again until the returned one is dropped.
+    ///
+    /// [`ColorBuf`]: ../trait.ColorBuf.html
+    pub fn subregion(
+        &mut self,
+        start_x: u64,
+        start_y: u64,
+        width: u64,
+        height: u64,
+    ) -> Result<SubRegionColorBuf<'_, B>> {
+        let end_x = start_x
+            .checked_add(width)
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        let end_y = start_y
+            .checked_add(height)
+            .ok_or(ColorBufError::InvalidDimensions)?;
+        if end_x > self.width || end_y > self.height {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        Ok(SubRegionColorBuf {
+            backing: &mut *self.backing,
+            reg_x: self.reg_x + start_x,
+            reg_y: self.reg_y + start_y,
+            width,
+            height,
+        })
+    }
 }
 
 impl<'a, B> ColorBuf for SubRegionColorBuf<'a, B>
@@ -78,3 +146,3327 @@ where
         self.height
     }
 }
+
+/// Repeating region.
+///
+/// This [`ColorBuf`] repeats another [`ColorBuf`] in both directions, up to the given width and
+/// height. Both reading and writing wrap around the edges of the backing [`ColorBuf`], so writing
+/// a pixel also changes every other pixel that maps to the same backing pixel.
+///
+/// [`ColorBuf`]: ../trait.ColorBuf.html
+pub struct TiledColorBuf<'a, B>
+where
+    B: 'a + ColorBuf,
+{
+    backing: &'a mut B,
+
+    width: u64,
+    height: u64,
+}
+
+impl<'a, B> TiledColorBuf<'a, B>
+where
+    B: 'a + ColorBuf,
+{
+    /// Returns a new `width` by `height` buffer repeating `backing`.
+    ///
+    /// Returns `ColorBufError::InvalidDimensions` if `backing` is empty.
+    pub fn new(backing: &'a mut B, width: u64, height: u64) -> Result<TiledColorBuf<'a, B>> {
+        if backing.get_width() == 0 || backing.get_height() == 0 {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        Ok(TiledColorBuf {
+            backing,
+            width,
+            height,
+        })
+    }
+}
+
+impl<'a, B> ColorBuf for TiledColorBuf<'a, B>
+where
+    B: 'a + ColorBuf,
+{
+    fn get_pixel(&self, x: u64, y: u64) -> Result<Color> {
+        if x >= self.width || y >= self.height {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        self.backing
+            .get_pixel(x % self.backing.get_width(), y % self.backing.get_height())
+    }
+
+    fn set_pixel(&mut self, x: u64, y: u64, color: &Color) -> Result<()> {
+        if x >= self.width || y >= self.height {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        let backing_x = x % self.backing.get_width();
+        let backing_y = y % self.backing.get_height();
+        self.backing.set_pixel(backing_x, backing_y, color)
+    }
+
+    fn get_width(&self) -> u64 {
+        self.width
+    }
+
+    fn get_height(&self) -> u64 {
+        self.height
+    }
+}
+
+/// Edge-extending region.
+///
+/// This [`ColorBuf`] reads from another [`ColorBuf`], but instead of failing for coordinates
+/// outside of the bounds, they are clamped to the nearest edge. The coordinates are interpreted as
+/// two's complement, so coordinates that wrapped below zero (e.g. `0u64.wrapping_sub(1)`) are
+/// clamped to the left or top edge.
+///
+/// This [`ColorBuf`] is read-only, so writing to it always fails with `ColorBufError::ReadOnly`.
+///
+/// [`ColorBuf`]: ../trait.ColorBuf.html
+pub struct ClampedColorBuf<'a, B>
+where
+    B: 'a + ColorBuf,
+{
+    backing: &'a B,
+}
+
+impl<'a, B> ClampedColorBuf<'a, B>
+where
+    B: 'a + ColorBuf,
+{
+    /// Returns a new buffer clamping reads to the edges of `backing`.
+    ///
+    /// Returns `ColorBufError::InvalidDimensions` if `backing` is empty.
+    pub fn new(backing: &'a B) -> Result<ClampedColorBuf<'a, B>> {
+        if backing.get_width() == 0 || backing.get_height() == 0 {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        Ok(ClampedColorBuf { backing })
+    }
+}
+
+impl<'a, B> ColorBuf for ClampedColorBuf<'a, B>
+where
+    B: 'a + ColorBuf,
+{
+    fn get_pixel(&self, x: u64, y: u64) -> Result<Color> {
+        let clamp = |pos: u64, len: u64| (pos as i64).max(0).min(len as i64 - 1) as u64;
+        self.backing.get_pixel(
+            clamp(x, self.backing.get_width()),
+            clamp(y, self.backing.get_height()),
+        )
+    }
+
+    fn set_pixel(&mut self, _x: u64, _y: u64, _color: &Color) -> Result<()> {
+        Err(ColorBufError::ReadOnly)
+    }
+
+    fn get_width(&self) -> u64 {
+        self.backing.get_width()
+    }
+
+    fn get_height(&self) -> u64 {
+        self.backing.get_height()
+    }
+}
+
+/// Fills a rectangle of `buf` with `color`.
+///
+/// The rectangle starts at `(x, y)` and is `width` pixels wide and `height` pixels high. It must
+/// lie fully inside `buf`, otherwise `ColorBufError::InvalidDimensions` is returned. This calls
+/// [`ColorBuf::fill_rect`], so buffers with a faster way of filling rectangles use it.
+///
+/// [`ColorBuf::fill_rect`]: ../trait.ColorBuf.html#method.fill_rect
+pub fn fill_rect<B>(
+    buf: &mut B,
+    x: u64,
+    y: u64,
+    width: u64,
+    height: u64,
+    color: &Color,
+) -> Result<()>
+where
+    B: ColorBuf,
+{
+    buf.fill_rect(x, y, width, height, color)
+}
+
+/// Draws the border of a rectangle onto `buf`.
+///
+/// The rectangle starts at `(x, y)` and is `width` pixels wide and `height` pixels high. It must
+/// lie fully inside `buf`, otherwise `ColorBufError::InvalidDimensions` is returned. The border
+/// grows inwards by `thickness` pixels, so a border thicker than half of the rectangle fills it.
+pub fn draw_rect<B>(
+    buf: &mut B,
+    x: u64,
+    y: u64,
+    width: u64,
+    height: u64,
+    color: &Color,
+    thickness: u64,
+) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let mut region = SubRegionColorBuf::new(buf, x, y, width, height)?;
+    let horizontal = thickness.min(height);
+    let vertical = thickness.min(width);
+
+    fill_rect(&mut region, 0, 0, width, horizontal, color)?;
+    fill_rect(
+        &mut region,
+        0,
+        height - horizontal,
+        width,
+        horizontal,
+        color,
+    )?;
+    fill_rect(&mut region, 0, 0, vertical, height, color)?;
+    fill_rect(&mut region, width - vertical, 0, vertical, height, color)
+}
+
+/// Composites `src` on top of `dst`.
+///
+/// The top-left corner of `src` is placed at `(dst_x, dst_y)` within `dst`, and every pixel is
+/// blended with a gamma of `2.2`. Pixels that would fall outside of `dst` are clipped.
+pub fn blit<S, D>(dst: &mut D, src: &S, dst_x: u64, dst_y: u64) -> Result<()>
+where
+    S: ColorBuf,
+    D: ColorBuf,
+{
+    let width = src.get_width().min(dst.get_width().saturating_sub(dst_x));
+    let height = src.get_height().min(dst.get_height().saturating_sub(dst_y));
+
+    for y in 0..height {
+        for x in 0..width {
+            let src_color = src.get_pixel(x, y)?;
+            let dst_color = dst.get_pixel(dst_x + x, dst_y + y)?;
+            dst.set_pixel(
+                dst_x + x,
+                dst_y + y,
+                &dst_color.blend_with_gamma(src_color, 2.2f32),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Composites `src` on top of `dst` through `mask`.
+///
+/// This works like [`blit`], except that the alpha of every pixel of `src` is scaled by the alpha
+/// of the corresponding pixel of `mask`. Pixels where the mask is fully transparent are left
+/// untouched. Returns `ColorBufError::InvalidDimensions` if `src` and `mask` differ in size.
+///
+/// [`blit`]: fn.blit.html
+pub fn blit_masked<D, S, M>(dst: &mut D, src: &S, mask: &M, dst_x: u64, dst_y: u64) -> Result<()>
+where
+    D: ColorBuf,
+    S: ColorBuf,
+    M: ColorBuf,
+{
+    if src.dimensions() != mask.dimensions() {
+        return Err(ColorBufError::InvalidDimensions);
+    }
+    let width = src.get_width().min(dst.get_width().saturating_sub(dst_x));
+    let height = src.get_height().min(dst.get_height().saturating_sub(dst_y));
+
+    for y in 0..height {
+        for x in 0..width {
+            let coverage = mask.get_pixel(x, y)?.a;
+            if coverage == 0f32 {
+                continue;
+            }
+            let src_color = src.get_pixel(x, y)?;
+            let src_color = Color {
+                a: src_color.a * coverage,
+                ..src_color
+            };
+            let dst_color = dst.get_pixel(dst_x + x, dst_y + y)?;
+            dst.set_pixel(
+                dst_x + x,
+                dst_y + y,
+                &dst_color.blend_with_gamma(src_color, 2.2f32),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Composites `src` on top of `dst` using premultiplied alpha.
+///
+/// Like [`blit`], except that both pixels are premultiplied, combined with the premultiplied
+/// "over" operator and converted back to straight alpha before writing. This avoids mixing the
+/// color of fully transparent pixels into the result. Pixels that would fall outside of `dst` are
+/// clipped.
+///
+/// [`blit`]: fn.blit.html
+pub fn composite_premultiplied<D, S>(dst: &mut D, src: &S, dst_x: u64, dst_y: u64) -> Result<()>
+where
+    D: ColorBuf,
+    S: ColorBuf,
+{
+    let width = src.get_width().min(dst.get_width().saturating_sub(dst_x));
+    let height = src.get_height().min(dst.get_height().saturating_sub(dst_y));
+
+    for y in 0..height {
+        for x in 0..width {
+            let src_color = src.get_pixel(x, y)?.premultiplied();
+            let dst_color = dst.get_pixel(dst_x + x, dst_y + y)?.premultiplied();
+            let dst_weight = 1f32 - src_color.a;
+            let out = Color {
+                r: src_color.r + dst_color.r * dst_weight,
+                g: src_color.g + dst_color.g * dst_weight,
+                b: src_color.b + dst_color.b * dst_weight,
+                a: src_color.a + dst_color.a * dst_weight,
+            };
+            dst.set_pixel(dst_x + x, dst_y + y, &out.unpremultiplied())?;
+        }
+    }
+    Ok(())
+}
+
+/// Tells [`blend_mode`] how to combine the channels of two pixels.
+///
+/// [`blend_mode`]: fn.blend_mode.html
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    /// `dst + src`
+    Add,
+    /// `dst * src`
+    Multiply,
+    /// `1 - (1 - dst) * (1 - src)`
+    Screen,
+    /// `|dst - src|`
+    Difference,
+}
+
+/// Combines every pixel of `src` into the corresponding pixel of `dst` according to `mode`.
+///
+/// The color channels are combined independently and clamped to `[0, 1]`, while the alpha of
+/// `dst` is left as is. Returns `ColorBufError::InvalidDimensions` if `dst` and `src` differ in
+/// size.
+pub fn blend_mode<A, B>(dst: &mut A, src: &B, mode: BlendMode) -> Result<()>
+where
+    A: ColorBuf,
+    B: ColorBuf,
+{
+    if dst.dimensions() != src.dimensions() {
+        return Err(ColorBufError::InvalidDimensions);
+    }
+
+    let combine = |dst: f32, src: f32| {
+        let combined = match mode {
+            BlendMode::Add => dst + src,
+            BlendMode::Multiply => dst * src,
+            BlendMode::Screen => 1f32 - (1f32 - dst) * (1f32 - src),
+            BlendMode::Difference => (dst - src).abs(),
+        };
+        combined.clamp(0f32, 1f32)
+    };
+    for y in 0..dst.get_height() {
+        for x in 0..dst.get_width() {
+            let src_color = src.get_pixel(x, y)?;
+            let dst_color = dst.get_pixel(x, y)?;
+            let color = Color {
+                r: combine(dst_color.r, src_color.r),
+                g: combine(dst_color.g, src_color.g),
+                b: combine(dst_color.b, src_color.b),
+                a: dst_color.a,
+            };
+            dst.set_pixel(x, y, &color)?;
+        }
+    }
+    Ok(())
+}
+
+/// Mirrors `buf` in place so that the left and right sides are swapped.
+pub fn flip_horizontal<B>(buf: &mut B) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let width = buf.get_width();
+    for y in 0..buf.get_height() {
+        for x in 0..width / 2 {
+            let left = buf.get_pixel(x, y)?;
+            let right = buf.get_pixel(width - 1 - x, y)?;
+            buf.set_pixel(x, y, &right)?;
+            buf.set_pixel(width - 1 - x, y, &left)?;
+        }
+    }
+    Ok(())
+}
+
+/// Mirrors `buf` in place so that the top and bottom sides are swapped.
+pub fn flip_vertical<B>(buf: &mut B) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let height = buf.get_height();
+    for y in 0..height / 2 {
+        for x in 0..buf.get_width() {
+            let top = buf.get_pixel(x, y)?;
+            let bottom = buf.get_pixel(x, height - 1 - y)?;
+            buf.set_pixel(x, y, &bottom)?;
+            buf.set_pixel(x, height - 1 - y, &top)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns a copy of `src` rotated clockwise by 90 degrees.
+///
+/// The width of the returned buffer is the height of `src` and vice versa.
+pub fn rotate90<B>(src: &B) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let width = src.get_width();
+    let height = src.get_height();
+    let mut dst = VecColorBuf::new(height, width);
+    for y in 0..height {
+        for x in 0..width {
+            let color = src.get_pixel(x, y)?;
+            dst.set_pixel(height - 1 - y, x, &color)?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Returns a copy of `src` rotated by 180 degrees.
+pub fn rotate180<B>(src: &B) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let width = src.get_width();
+    let height = src.get_height();
+    let mut dst = VecColorBuf::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let color = src.get_pixel(x, y)?;
+            dst.set_pixel(width - 1 - x, height - 1 - y, &color)?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Returns a copy of `src` rotated clockwise by 270 degrees.
+///
+/// The width of the returned buffer is the height of `src` and vice versa.
+pub fn rotate270<B>(src: &B) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let width = src.get_width();
+    let height = src.get_height();
+    let mut dst = VecColorBuf::new(height, width);
+    for y in 0..height {
+        for x in 0..width {
+            let color = src.get_pixel(x, y)?;
+            dst.set_pixel(y, width - 1 - x, &color)?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Returns a copy of `src` scaled to `new_width` by `new_height` using nearest-neighbor sampling.
+///
+/// Returns `ColorBufError::InvalidDimensions` if either `src` or the new size is empty.
+pub fn resize_nearest<B>(src: &B, new_width: u64, new_height: u64) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let width = src.get_width();
+    let height = src.get_height();
+    if new_width == 0 || new_height == 0 || width == 0 || height == 0 {
+        return Err(ColorBufError::InvalidDimensions);
+    }
+
+    let mut dst = VecColorBuf::new(new_width, new_height);
+    for y in 0..new_height {
+        // Sample at the center of the destination pixel.
+        let src_y = ((2 * y + 1) * height) / (2 * new_height);
+        for x in 0..new_width {
+            let src_x = ((2 * x + 1) * width) / (2 * new_width);
+            let color = src.get_pixel(src_x, src_y)?;
+            dst.set_pixel(x, y, &color)?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Returns a copy of `src` scaled to `new_width` by `new_height` using bilinear filtering.
+///
+/// Every pixel of the new buffer is a weighted blend of the four nearest pixels of `src`. Samples
+/// that would fall outside of `src` are clamped to its edges.
+///
+/// Returns `ColorBufError::InvalidDimensions` if either `src` or the new size is empty.
+pub fn resize_bilinear<B>(src: &B, new_width: u64, new_height: u64) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let width = src.get_width();
+    let height = src.get_height();
+    if new_width == 0 || new_height == 0 || width == 0 || height == 0 {
+        return Err(ColorBufError::InvalidDimensions);
+    }
+
+    let mut dst = VecColorBuf::new(new_width, new_height);
+    for y in 0..new_height {
+        let (y0, y1, ty) = bilinear_sample_points(y, height, new_height);
+        for x in 0..new_width {
+            let (x0, x1, tx) = bilinear_sample_points(x, width, new_width);
+
+            let top = src.get_pixel(x0, y0)?.lerp(src.get_pixel(x1, y0)?, tx);
+            let bottom = src.get_pixel(x0, y1)?.lerp(src.get_pixel(x1, y1)?, tx);
+            dst.set_pixel(x, y, &top.lerp(bottom, ty))?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Maps the destination coordinate `pos` back to the two source coordinates surrounding it, and
+/// the weight of the latter one.
+fn bilinear_sample_points(pos: u64, src_len: u64, dst_len: u64) -> (u64, u64, f32) {
+    let src_pos = ((pos as f32 + 0.5f32) * src_len as f32 / dst_len as f32 - 0.5f32)
+        .max(0f32)
+        .min((src_len - 1) as f32);
+    let first = src_pos.floor() as u64;
+    let second = (first + 1).min(src_len - 1);
+
+    (first, second, src_pos - first as f32)
+}
+
+/// Returns a copy of a rectangle of `src`.
+///
+/// The rectangle starts at `(x, y)` and is `width` pixels wide and `height` pixels high. It must
+/// lie fully inside `src`, otherwise `ColorBufError::InvalidDimensions` is returned.
+pub fn crop<B>(src: &B, x: u64, y: u64, width: u64, height: u64) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let end_x = x
+        .checked_add(width)
+        .ok_or(ColorBufError::InvalidDimensions)?;
+    let end_y = y
+        .checked_add(height)
+        .ok_or(ColorBufError::InvalidDimensions)?;
+    if end_x > src.get_width() || end_y > src.get_height() {
+        return Err(ColorBufError::InvalidDimensions);
+    }
+
+    let mut dst = VecColorBuf::new(width, height);
+    for dst_y in 0..height {
+        for dst_x in 0..width {
+            let color = src.get_pixel(x + dst_x, y + dst_y)?;
+            dst.set_pixel(dst_x, dst_y, &color)?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Returns a copy of `src` stored as a bitmap with the given `format` and `depth`.
+///
+/// Every pixel goes through its [`Color`] representation and is written to the bitmap with
+/// [`ColorBuf::set_pixel`]. RGB and BGR formats store the color channels divided by alpha, so
+/// translucent pixels come out brighter and fully transparent ones come out black, while
+/// `ColorFormat::Grayscale` stores the luminance regardless of alpha.
+/// Pixels of sources without alpha are opaque in formats with alpha. The returned bitmap is
+/// tightly packed, see [`BitmapColorBuf::empty`].
+///
+/// [`Color`]: ../struct.Color.html
+/// [`ColorBuf::set_pixel`]: ../trait.ColorBuf.html#tymethod.set_pixel
+/// [`BitmapColorBuf::empty`]: ../bitmap/struct.BitmapColorBuf.html#method.empty
+pub fn convert_format<B>(src: &B, format: ColorFormat, depth: BitDepth) -> Result<BitmapColorBuf>
+where
+    B: ColorBuf,
+{
+    let mut dst = BitmapColorBuf::empty(format, depth, src.get_height(), src.get_width());
+    dst.copy_from(src)?;
+    Ok(dst)
+}
+
+/// Returns a grayscale copy of a single channel of `src`.
+///
+/// Every pixel of the returned buffer is opaque, with its color channels set to the `channel` of
+/// the corresponding pixel of `src`.
+pub fn extract_channel<B>(src: &B, channel: Channel) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let mut dst = VecColorBuf::new(src.get_width(), src.get_height());
+    for pixel in iter_pixels(src) {
+        let (x, y, color) = pixel?;
+        let value = color.channel(channel);
+        let gray = Color {
+            r: value,
+            g: value,
+            b: value,
+            a: 1f32,
+        };
+        dst.set_pixel(x, y, &gray)?;
+    }
+    Ok(dst)
+}
+
+/// Returns the alpha channel of `src` as a grayscale buffer, for example to use as a mask.
+///
+/// See [`extract_channel`].
+///
+/// [`extract_channel`]: fn.extract_channel.html
+pub fn extract_alpha<B>(src: &B) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    extract_channel(src, Channel::A)
+}
+
+/// Iterates over every pixel of `buf` in row-major order.
+///
+/// Every item is the `x` and `y` of the pixel together with its color. A row that can't be read
+/// gives a single error in place of its pixels.
+pub fn iter_pixels<B>(buf: &B) -> impl Iterator<Item = Result<(u64, u64, Color)>> + '_
+where
+    B: ColorBuf,
+{
+    iter_rows(buf).enumerate().flat_map(|(y, row)| {
+        let (row, error) = match row {
+            Ok(row) => (row, None),
+            Err(error) => (Vec::new(), Some(Err(error))),
+        };
+        row.into_iter()
+            .enumerate()
+            .map(move |(x, color)| Ok((x as u64, y as u64, color)))
+            .chain(error)
+    })
+}
+
+/// Iterates over the rows of `buf` from top to bottom.
+///
+/// Every item holds the colors of one row from left to right, as read by [`ColorBuf::get_row`].
+/// [`VecColorBuf::rows`] gives the rows without copying them.
+///
+/// [`ColorBuf::get_row`]: ../trait.ColorBuf.html#method.get_row
+/// [`VecColorBuf::rows`]: ../vec/struct.VecColorBuf.html#method.rows
+pub fn iter_rows<B>(buf: &B) -> impl Iterator<Item = Result<Vec<Color>>> + '_
+where
+    B: ColorBuf,
+{
+    (0..buf.get_height()).map(move |y| buf.get_row(y))
+}
+
+/// Returns the amount of pixels in `buf` as a `usize`, for allocating per-pixel scratch space.
+///
+/// Returns `ColorBufError::InvalidDimensions` if the amount doesn't fit.
+fn buffer_len<B>(buf: &B) -> Result<usize>
+where
+    B: ColorBuf,
+{
+    buf.pixel_count()
+        .and_then(|len| usize::try_from(len).ok())
+        .ok_or(ColorBufError::InvalidDimensions)
+}
+
+/// Replaces every pixel of `buf` with the result of calling `f` on it.
+pub fn map_pixels<B, F>(buf: &mut B, f: F) -> Result<()>
+where
+    B: ColorBuf,
+    F: Fn(Color) -> Color,
+{
+    for y in 0..buf.get_height() {
+        for x in 0..buf.get_width() {
+            let color = buf.get_pixel(x, y)?;
+            buf.set_pixel(x, y, &f(color))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces every pixel of `buf` with the result of calling `f` on it, processing the rows in
+/// parallel.
+///
+/// The rows are split with [`ParRowsMut::par_rows_mut`] and mapped in place using `rayon`, so
+/// `f` may be called in any order. The result is the same as with [`map_pixels`]. Only available
+/// with the `rayon` feature.
+///
+/// [`ParRowsMut::par_rows_mut`]: ../trait.ParRowsMut.html#tymethod.par_rows_mut
+/// [`map_pixels`]: fn.map_pixels.html
+#[cfg(feature = "rayon")]
+pub fn par_map_pixels<B, F>(buf: &mut B, f: F)
+where
+    B: ParRowsMut,
+    F: Fn(Color) -> Color + Sync,
+{
+    use rayon::prelude::*;
+
+    buf.par_rows_mut().for_each(|row| {
+        for color in row.iter_mut() {
+            *color = f(*color);
+        }
+    });
+}
+
+/// Converts every pixel of `buf` to grayscale.
+///
+/// See [`Color::to_grayscale`].
+///
+/// [`Color::to_grayscale`]: ../struct.Color.html#method.to_grayscale
+pub fn grayscale<B>(buf: &mut B) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| color.to_grayscale())
+}
+
+/// Shifts the hue of every pixel of `buf` by `degrees`.
+///
+/// The hue wraps around `360` degrees, and the saturation, value and alpha are kept as is.
+pub fn rotate_hue<B>(buf: &mut B, degrees: f32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| {
+        let (h, s, v) = color.to_hsv();
+        Color::from_hsv(h + degrees, s, v, color.a)
+    })
+}
+
+/// Adjusts the brightness and contrast of every pixel of `buf`.
+///
+/// Every color channel is mapped as `(channel - 0.5) * contrast + 0.5 + brightness` and then
+/// clamped to `[0, 1]`. Alpha is left as is.
+pub fn adjust_brightness_contrast<B>(buf: &mut B, brightness: f32, contrast: f32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let adjust =
+        |channel: f32| ((channel - 0.5f32) * contrast + 0.5f32 + brightness).clamp(0f32, 1f32);
+    map_pixels(buf, |color| Color {
+        r: adjust(color.r),
+        g: adjust(color.g),
+        b: adjust(color.b),
+        a: color.a,
+    })
+}
+
+/// Adjusts the saturation of every pixel of `buf`.
+///
+/// Every pixel is interpolated between its grayscale value at a `factor` of `0` and its original
+/// value at a `factor` of `1`. Factors above `1` push the colors further away from gray. The color
+/// channels are clamped to `[0, 1]` and alpha is left as is. See [`Color::to_grayscale`].
+///
+/// [`Color::to_grayscale`]: ../struct.Color.html#method.to_grayscale
+pub fn adjust_saturation<B>(buf: &mut B, factor: f32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| {
+        let gray = color.to_grayscale().r;
+        let adjust = |channel: f32| (gray + (channel - gray) * factor).clamp(0f32, 1f32);
+        Color {
+            r: adjust(color.r),
+            g: adjust(color.g),
+            b: adjust(color.b),
+            a: color.a,
+        }
+    })
+}
+
+/// Applies a gamma curve to every pixel of `buf`.
+///
+/// Every color channel is raised to the power of `1 / gamma`, so a `gamma` above `1` brightens
+/// the buffer. See [`Color::with_gamma`].
+///
+/// [`Color::with_gamma`]: ../struct.Color.html#method.with_gamma
+pub fn apply_gamma<B>(buf: &mut B, gamma: f32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| color.with_gamma(gamma))
+}
+
+/// Fills `buf` with a gradient going from `left` on the leftmost column to `right` on the
+/// rightmost column.
+pub fn fill_gradient_horizontal<B>(buf: &mut B, left: Color, right: Color) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let width = buf.get_width();
+    for x in 0..width {
+        let color = left.lerp(right, gradient_position(x, width));
+        for y in 0..buf.get_height() {
+            buf.set_pixel(x, y, &color)?;
+        }
+    }
+    Ok(())
+}
+
+/// Fills `buf` with a gradient going from `top` on the topmost row to `bottom` on the
+/// bottommost row.
+pub fn fill_gradient_vertical<B>(buf: &mut B, top: Color, bottom: Color) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let height = buf.get_height();
+    for y in 0..height {
+        let color = top.lerp(bottom, gradient_position(y, height));
+        for x in 0..buf.get_width() {
+            buf.set_pixel(x, y, &color)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns how far along a gradient of `len` pixels the pixel at `pos` is.
+fn gradient_position(pos: u64, len: u64) -> f32 {
+    if len <= 1 {
+        0f32
+    } else {
+        pos as f32 / (len - 1) as f32
+    }
+}
+
+/// Composites `color` on top of every pixel of `buf` with the given `gamma`.
+///
+/// See [`Color::blend_with_gamma`].
+///
+/// [`Color::blend_with_gamma`]: ../struct.Color.html#method.blend_with_gamma
+pub fn overlay_color<B>(buf: &mut B, color: &Color, gamma: f32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |existing| existing.blend_with_gamma(*color, gamma))
+}
+
+/// Returns a copy of `src` where every pixel is the average of the pixels within `radius`.
+///
+/// The blur is done in two passes, first horizontally and then vertically. Pixels outside of
+/// `src` are treated as copies of the nearest edge pixel. Every pass keeps a running sum of the
+/// window, so the cost doesn't depend on `radius`.
+pub fn box_blur<B>(src: &B, radius: u64) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let width = src.get_width();
+    let height = src.get_height();
+    let mut horizontal = Vec::with_capacity(buffer_len(src)?);
+    let mut dst = VecColorBuf::new(width, height);
+    if width == 0 || height == 0 {
+        return Ok(dst);
+    }
+
+    for y in 0..height {
+        horizontal.extend(box_blur_line(&src.get_row(y)?, radius));
+    }
+
+    let width = width as usize;
+    for x in 0..width {
+        let column: Vec<Color> = horizontal.iter().skip(x).step_by(width).copied().collect();
+        for (y, color) in box_blur_line(&column, radius).iter().enumerate() {
+            dst.set_pixel(x as u64, y as u64, color)?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Returns every pixel of `line` averaged with the pixels within `radius` of it.
+///
+/// The ends of `line` are extended with copies of the edge pixels, which must exist.
+fn box_blur_line(line: &[Color], radius: u64) -> Vec<Color> {
+    let last = line.len() - 1;
+    let sample = |i: i128| line[i.clamp(0, last as i128) as usize];
+    let add = |sum: &mut [f64; 4], color: Color, count: f64| {
+        sum[0] += color.r as f64 * count;
+        sum[1] += color.g as f64 * count;
+        sum[2] += color.b as f64 * count;
+        sum[3] += color.a as f64 * count;
+    };
+
+    // Start with the window around the first pixel. Only the part within `line` needs to be
+    // visited, the rest are copies of the edge pixels.
+    let radius = i128::from(radius);
+    let mut sum = [0f64; 4];
+    add(&mut sum, line[0], radius as f64);
+    for &color in &line[..=radius.min(last as i128) as usize] {
+        add(&mut sum, color, 1f64);
+    }
+    add(&mut sum, line[last], (radius - last as i128).max(0) as f64);
+
+    let window = (2 * radius + 1) as f64;
+    let mut blurred = Vec::with_capacity(line.len());
+    for x in 0..line.len() as i128 {
+        blurred.push(Color {
+            r: (sum[0] / window) as f32,
+            g: (sum[1] / window) as f32,
+            b: (sum[2] / window) as f32,
+            a: (sum[3] / window) as f32,
+        });
+        add(&mut sum, sample(x + radius + 1), 1f64);
+        add(&mut sum, sample(x - radius), -1f64);
+    }
+    blurred
+}
+
+/// Returns a copy of `src` convolved with a 3x3 `kernel`.
+///
+/// The kernel is indexed as `kernel[row][column]`, with the pixel itself being in the center.
+/// Every color channel is divided by `divisor`, offset by `bias` and then clamped to `[0, 1]`.
+/// Pixels outside of `src` are treated as copies of the nearest edge pixel. The alpha channel is
+/// not convolved, but copied from `src` as is.
+pub fn convolve3x3<B>(
+    src: &B,
+    kernel: [[f32; 3]; 3],
+    divisor: f32,
+    bias: f32,
+) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let width = src.get_width();
+    let height = src.get_height();
+    let mut dst = VecColorBuf::new(width, height);
+    if width == 0 || height == 0 {
+        return Ok(dst);
+    }
+
+    let src = ClampedColorBuf::new(src)?;
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Color::TRANSPARENT;
+            for (row, weights) in kernel.iter().enumerate() {
+                for (column, weight) in weights.iter().enumerate() {
+                    let color = src.get_pixel(
+                        (x + column as u64).wrapping_sub(1),
+                        (y + row as u64).wrapping_sub(1),
+                    )?;
+                    sum.r += color.r * weight;
+                    sum.g += color.g * weight;
+                    sum.b += color.b * weight;
+                }
+            }
+
+            let convolve = |channel: f32| (channel / divisor + bias).clamp(0f32, 1f32);
+            let color = Color {
+                r: convolve(sum.r),
+                g: convolve(sum.g),
+                b: convolve(sum.b),
+                a: src.get_pixel(x, y)?.a,
+            };
+            dst.set_pixel(x, y, &color)?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Returns a copy of `src` shrunk by `factor` in both directions.
+///
+/// Every pixel of the returned buffer is the average of a `factor` by `factor` block of `src`.
+/// Returns `ColorBufError::InvalidDimensions` if `factor` is zero, or if the dimensions of `src`
+/// aren't divisible by it.
+pub fn downsample<B>(src: &B, factor: u64) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let (width, height) = src.dimensions();
+    if factor == 0 || width % factor != 0 || height % factor != 0 {
+        return Err(ColorBufError::InvalidDimensions);
+    }
+    average_blocks(src, width / factor, height / factor)
+}
+
+/// Returns the mipmap chain of `src`.
+///
+/// The first level is a copy of `src`, and every following level halves the dimensions of the
+/// previous one by averaging blocks of pixels, rounding down but never going below `1`. The last
+/// level is `1` by `1` pixels.
+pub fn generate_mipmaps<B>(src: &B) -> Result<Vec<VecColorBuf>>
+where
+    B: ColorBuf,
+{
+    let (width, height) = src.dimensions();
+    let mut levels = vec![crop(src, 0, 0, width, height)?];
+    if width == 0 || height == 0 {
+        return Ok(levels);
+    }
+
+    loop {
+        let previous = &levels[levels.len() - 1];
+        let (width, height) = previous.dimensions();
+        if width == 1 && height == 1 {
+            break;
+        }
+        let next = average_blocks(previous, (width / 2).max(1), (height / 2).max(1))?;
+        levels.push(next);
+    }
+    Ok(levels)
+}
+
+/// Returns a copy of `src` shrunk to `new_width` by `new_height` by averaging blocks of pixels.
+///
+/// The blocks are spread evenly over `src`, so when the dimensions aren't divisible, some of the
+/// blocks are a pixel wider or higher than the others. The new dimensions must not be larger than
+/// the ones of `src`.
+fn average_blocks<B>(src: &B, new_width: u64, new_height: u64) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let (width, height) = src.dimensions();
+    let mut dst = VecColorBuf::new(new_width, new_height);
+    for y in 0..new_height {
+        let rows = (y * height / new_height)..((y + 1) * height / new_height);
+        for x in 0..new_width {
+            let columns = (x * width / new_width)..((x + 1) * width / new_width);
+
+            let mut block = Vec::new();
+            for src_y in rows.clone() {
+                for src_x in columns.clone() {
+                    block.push(src.get_pixel(src_x, src_y)?);
+                }
+            }
+            dst.set_pixel(x, y, &average(block.into_iter().map(Ok))?)?;
+        }
+    }
+    Ok(dst)
+}
+
+/// Returns the average of every channel of `colors`.
+///
+/// The average of no colors at all is transparent black.
+fn average<I>(colors: I) -> Result<Color>
+where
+    I: Iterator<Item = Result<Color>>,
+{
+    // Summing into `f64`s keeps the precision when averaging whole buffers.
+    let mut sum = [0f64; 4];
+    let mut count = 0;
+    for color in colors {
+        let color = color?;
+        sum[0] += color.r as f64;
+        sum[1] += color.g as f64;
+        sum[2] += color.b as f64;
+        sum[3] += color.a as f64;
+        count += 1;
+    }
+    if count == 0 {
+        return Ok(Color::TRANSPARENT);
+    }
+
+    Ok(Color {
+        r: (sum[0] / count as f64) as f32,
+        g: (sum[1] / count as f64) as f32,
+        b: (sum[2] / count as f64) as f32,
+        a: (sum[3] / count as f64) as f32,
+    })
+}
+
+/// Returns the average of every channel of every pixel of `buf`.
+///
+/// The average of an empty buffer is transparent black.
+pub fn average_color<B>(buf: &B) -> Result<Color>
+where
+    B: ColorBuf,
+{
+    average(iter_pixels(buf).map(|pixel| pixel.map(|(_, _, color)| color)))
+}
+
+/// Returns the smallest and largest value of every channel of every pixel of `buf`.
+///
+/// The channels are compared independently, so neither color necessarily occurs in `buf`. The
+/// bounds of an empty buffer are both transparent black.
+pub fn bounds<B>(buf: &B) -> Result<(Color, Color)>
+where
+    B: ColorBuf,
+{
+    let mut bounds = None;
+    for pixel in iter_pixels(buf) {
+        let (_, _, color) = pixel?;
+        let (min, max) = bounds.unwrap_or((color, color));
+        bounds = Some((
+            Color {
+                r: min.r.min(color.r),
+                g: min.g.min(color.g),
+                b: min.b.min(color.b),
+                a: min.a.min(color.a),
+            },
+            Color {
+                r: max.r.max(color.r),
+                g: max.g.max(color.g),
+                b: max.b.max(color.b),
+                a: max.a.max(color.a),
+            },
+        ));
+    }
+    Ok(bounds.unwrap_or((Color::TRANSPARENT, Color::TRANSPARENT)))
+}
+
+/// Inverts the color channels of every pixel of `buf`.
+///
+/// See [`Color::inverted`].
+///
+/// [`Color::inverted`]: ../struct.Color.html#method.inverted
+pub fn invert<B>(buf: &mut B) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| color.inverted())
+}
+
+/// Turns every pixel of `buf` either black or white depending on its luminance.
+///
+/// Pixels with a luminance above `level` become white and the rest become black. Alpha is left as
+/// is. See [`Color::to_grayscale`].
+///
+/// [`Color::to_grayscale`]: ../struct.Color.html#method.to_grayscale
+pub fn threshold<B>(buf: &mut B, level: f32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| {
+        let value = if color.to_grayscale().r > level {
+            1f32
+        } else {
+            0f32
+        };
+        Color {
+            r: value,
+            g: value,
+            b: value,
+            a: color.a,
+        }
+    })
+}
+
+/// Maps every color channel of `buf` through a 256 entry lookup table.
+///
+/// Each channel is quantized to 8 bits as with [`Color::to_rgba8`], and that value is used as an
+/// index into `lut`. Alpha is left as is.
+///
+/// [`Color::to_rgba8`]: ../struct.Color.html#method.to_rgba8
+pub fn apply_lut<B>(buf: &mut B, lut: &[f32; 256]) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| {
+        let (r, g, b, _) = color.to_rgba8();
+        Color {
+            r: lut[r as usize],
+            g: lut[g as usize],
+            b: lut[b as usize],
+            a: color.a,
+        }
+    })
+}
+
+/// A three dimensional color lookup table, as used by [`apply_lut3d`].
+///
+/// The table is a `size` by `size` by `size` grid of RGB colors spanning the RGB cube. Like in
+/// `.cube` files, the entries are ordered with red changing the fastest and blue the slowest.
+///
+/// [`apply_lut3d`]: fn.apply_lut3d.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3d {
+    size: usize,
+    data: Vec<[f32; 3]>,
+}
+
+impl Lut3d {
+    /// Returns a new lookup table with `size` entries along every axis.
+    ///
+    /// Returns `ColorBufError::InvalidDimensions` if `size` is below `2` or `data` doesn't hold
+    /// exactly `size * size * size` entries.
+    pub fn new(size: usize, data: Vec<[f32; 3]>) -> Result<Lut3d> {
+        if data.len() != Lut3d::entry_count(size)? {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        Ok(Lut3d { size, data })
+    }
+
+    /// Returns a lookup table with `size` entries along every axis that maps every color to
+    /// itself.
+    ///
+    /// Returns `ColorBufError::InvalidDimensions` if `size` is below `2`.
+    pub fn identity(size: usize) -> Result<Lut3d> {
+        let step = |i: usize| i as f32 / (size - 1) as f32;
+        let mut data = Vec::with_capacity(Lut3d::entry_count(size)?);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push([step(r), step(g), step(b)]);
+                }
+            }
+        }
+        Ok(Lut3d { size, data })
+    }
+
+    /// Returns `size * size * size`, checking that `size` is usable for a lookup table.
+    fn entry_count(size: usize) -> Result<usize> {
+        if size < 2 {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        size.checked_mul(size)
+            .and_then(|len| len.checked_mul(size))
+            .ok_or(ColorBufError::InvalidDimensions)
+    }
+
+    /// Returns the amount of entries along every axis.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.data[(b * self.size + g) * self.size + r]
+    }
+
+    /// Maps `color` through the table, trilinearly interpolating between the closest entries.
+    fn lookup(&self, color: &Color) -> Color {
+        let sample_points = |channel: f32| {
+            let pos = channel.clamp(0f32, 1f32) * (self.size - 1) as f32;
+            let first = (pos.floor() as usize).min(self.size - 2);
+            (first, pos - first as f32)
+        };
+        let (r, tr) = sample_points(color.r);
+        let (g, tg) = sample_points(color.g);
+        let (b, tb) = sample_points(color.b);
+
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+        let along_r = |g: usize, b: usize| lerp(self.entry(r, g, b), self.entry(r + 1, g, b), tr);
+        let near = lerp(along_r(g, b), along_r(g + 1, b), tg);
+        let far = lerp(along_r(g, b + 1), along_r(g + 1, b + 1), tg);
+        let [r, g, b] = lerp(near, far, tb);
+
+        Color {
+            r,
+            g,
+            b,
+            a: color.a,
+        }
+    }
+}
+
+/// Maps the color channels of every pixel of `buf` through a 3D lookup table.
+///
+/// The colors between the entries of `lut` are trilinearly interpolated, and channels outside of
+/// `[0, 1]` are clamped to the edge of the table first. Alpha is left as is.
+pub fn apply_lut3d<B>(buf: &mut B, lut: &Lut3d) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| lut.lookup(&color))
+}
+
+/// Reduces every color channel of `buf` to `levels` evenly spaced values.
+///
+/// Each channel is clamped to `[0, 1]` and rounded to the nearest level, so with `levels` of `2`
+/// every channel becomes either `0` or `1`. Alpha is left as is. If `levels` is below `2`, `buf` is
+/// left unchanged.
+pub fn posterize<B>(buf: &mut B, levels: u32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    if levels < 2 {
+        return Ok(());
+    }
+    map_pixels(buf, |color| Color {
+        r: quantize(color.r, levels),
+        g: quantize(color.g, levels),
+        b: quantize(color.b, levels),
+        a: color.a,
+    })
+}
+
+fn quantize(channel: f32, levels: u32) -> f32 {
+    let steps = (levels - 1) as f32;
+    (channel.clamp(0f32, 1f32) * steps).round() / steps
+}
+
+/// Reduces every color channel of `buf` to `levels` evenly spaced values using Floyd-Steinberg
+/// dithering.
+///
+/// Like [`posterize`], but the error made when rounding a pixel is spread to the neighbouring
+/// pixels that have not been visited yet, which hides banding in smooth areas. Alpha is left as is.
+/// If `levels` is below `2`, `buf` is left unchanged.
+///
+/// [`posterize`]: fn.posterize.html
+pub fn dither_floyd_steinberg<B>(buf: &mut B, levels: u32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    if levels < 2 {
+        return Ok(());
+    }
+    let (width, height) = buf.dimensions();
+    let mut errors = vec![[0f32; 3]; buffer_len(buf)?];
+    let index = |x: u64, y: u64| (y * width + x) as usize;
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = buf.get_pixel(x, y)?;
+            let error = errors[index(x, y)];
+            let wanted = [color.r + error[0], color.g + error[1], color.b + error[2]];
+            let quantized = [
+                quantize(wanted[0], levels),
+                quantize(wanted[1], levels),
+                quantize(wanted[2], levels),
+            ];
+            buf.set_pixel(
+                x,
+                y,
+                &Color {
+                    r: quantized[0],
+                    g: quantized[1],
+                    b: quantized[2],
+                    a: color.a,
+                },
+            )?;
+
+            let mut spread = |dx: i64, dy: u64, weight: f32| {
+                let nx = x as i64 + dx;
+                let ny = y + dy;
+                if nx < 0 || nx as u64 >= width || ny >= height {
+                    return;
+                }
+                let neighbour = &mut errors[index(nx as u64, ny)];
+                for channel in 0..3 {
+                    neighbour[channel] += (wanted[channel] - quantized[channel]) * weight;
+                }
+            };
+            spread(1, 0, 7f32 / 16f32);
+            spread(-1, 1, 3f32 / 16f32);
+            spread(0, 1, 5f32 / 16f32);
+            spread(1, 1, 1f32 / 16f32);
+        }
+    }
+    Ok(())
+}
+
+/// Counts how many pixels of `buf` fall into each of `bins` equally sized buckets.
+///
+/// The histograms of the red, green, blue and alpha channels are returned in that order. Channel
+/// values outside of `[0, 1]` are counted in the first or last bucket.
+pub fn histogram<B>(buf: &B, bins: usize) -> Result<[Vec<u64>; 4]>
+where
+    B: ColorBuf,
+{
+    let mut histogram = [vec![0; bins], vec![0; bins], vec![0; bins], vec![0; bins]];
+    if bins == 0 {
+        return Ok(histogram);
+    }
+
+    let bin = |channel: f32| ((channel.clamp(0f32, 1f32) * bins as f32) as usize).min(bins - 1);
+    for pixel in iter_pixels(buf) {
+        let (_, _, color) = pixel?;
+        histogram[0][bin(color.r)] += 1;
+        histogram[1][bin(color.g)] += 1;
+        histogram[2][bin(color.b)] += 1;
+        histogram[3][bin(color.a)] += 1;
+    }
+    Ok(histogram)
+}
+
+/// Counts the distinct colors of `buf`.
+///
+/// The colors are quantized to 8 bits per channel first, see [`Color::to_rgba8`]. Pixels that
+/// differ only in color but are both fully transparent still count as distinct colors.
+///
+/// [`Color::to_rgba8`]: ../struct.Color.html#method.to_rgba8
+pub fn count_colors<B>(buf: &B) -> Result<usize>
+where
+    B: ColorBuf,
+{
+    let colors = iter_pixels(buf)
+        .map(|pixel| pixel.map(|(_, _, color)| color.to_rgba8()))
+        .collect::<Result<HashSet<_>>>()?;
+    Ok(colors.len())
+}
+
+/// Picks a palette of at most `max_colors` colors representing `buf` using the median cut
+/// algorithm.
+///
+/// The pixels start out in a single box. The box with the widest range in any channel is
+/// repeatedly sorted along that channel and split at the median, until there are `max_colors`
+/// boxes or every box holds a single color. The palette consists of the average color of every
+/// box, in no particular order. An empty `buf` gives an empty palette.
+pub fn quantize_palette<B>(buf: &B, max_colors: usize) -> Result<Vec<Color>>
+where
+    B: ColorBuf,
+{
+    let colors = iter_pixels(buf)
+        .map(|pixel| pixel.map(|(_, _, color)| color))
+        .collect::<Result<Vec<Color>>>()?;
+    if colors.is_empty() || max_colors == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut boxes = vec![colors];
+    while boxes.len() < max_colors {
+        let (index, (channel, range)) = boxes
+            .iter()
+            .map(|colors| widest_channel(colors))
+            .enumerate()
+            .max_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            .unwrap();
+        if range <= 0f32 {
+            break;
+        }
+
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_by(|a, b| a.channel(channel).total_cmp(&b.channel(channel)));
+        let upper = lower.split_off(median_split(&lower, channel));
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+    boxes
+        .into_iter()
+        .map(|colors| average(colors.into_iter().map(Ok)))
+        .collect()
+}
+
+/// Returns the channel in which `colors` are spread the widest, along with the width.
+fn widest_channel(colors: &[Color]) -> (Channel, f32) {
+    let range = |channel: Channel| {
+        let values = colors.iter().map(|color| color.channel(channel));
+        let min = values.clone().fold(f32::INFINITY, f32::min);
+        let max = values.fold(f32::NEG_INFINITY, f32::max);
+        (channel, max - min)
+    };
+    [Channel::R, Channel::G, Channel::B, Channel::A]
+        .iter()
+        .map(|channel| range(*channel))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap()
+}
+
+/// Returns the index closest to the median of `colors`, sorted along `channel`, where the value of
+/// `channel` changes.
+///
+/// Splitting there keeps equal colors in the same box. The values must not all be equal.
+fn median_split(colors: &[Color], channel: Channel) -> usize {
+    let median = colors.len() / 2;
+    (1..colors.len())
+        .filter(|&i| colors[i - 1].channel(channel) != colors[i].channel(channel))
+        .min_by_key(|&i| (i as i64 - median as i64).abs())
+        .unwrap()
+}
+
+/// Draws a line from `(x0, y0)` to `(x1, y1)` onto `buf` using Bresenham's algorithm.
+///
+/// Both ends of the line are included. The coordinates may lie outside of `buf`, in which case the
+/// pixels that would fall outside are clipped.
+pub fn draw_line<B>(buf: &mut B, x0: i64, y0: i64, x1: i64, y1: i64, color: &Color) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let step_x = if x0 < x1 { 1 } else { -1 };
+    let step_y = if y0 < y1 { 1 } else { -1 };
+
+    let mut x = x0;
+    let mut y = y0;
+    let mut error = dx + dy;
+    loop {
+        plot(buf, x.into(), y.into(), color)?;
+        if x == x1 && y == y1 {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+    Ok(())
+}
+
+/// Draws the outline of a circle centered at `(cx, cy)` onto `buf`.
+///
+/// The circle is drawn with the midpoint circle algorithm. Pixels that would fall outside of `buf`
+/// are clipped.
+pub fn draw_circle<B>(buf: &mut B, cx: i64, cy: i64, radius: u64, color: &Color) -> Result<()>
+where
+    B: ColorBuf,
+{
+    // Past the reach, the whole outline lies outside of the buffer.
+    let radius = i128::from(radius);
+    if radius > circle_reach(buf, cx, cy) + 1 {
+        return Ok(());
+    }
+
+    let (cx, cy) = (i128::from(cx), i128::from(cy));
+    let mut x = radius;
+    let mut y = 0;
+    let mut error = 1 - x;
+    while x >= y {
+        for &(dx, dy) in [(x, y), (y, x)].iter() {
+            plot(buf, cx + dx, cy + dy, color)?;
+            plot(buf, cx - dx, cy + dy, color)?;
+            plot(buf, cx + dx, cy - dy, color)?;
+            plot(buf, cx - dx, cy - dy, color)?;
+        }
+
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+    Ok(())
+}
+
+/// Draws a filled circle centered at `(cx, cy)` onto `buf`.
+///
+/// Every pixel whose distance from the center is at most `radius` is set. Pixels that would fall
+/// outside of `buf` are clipped.
+pub fn fill_circle<B>(buf: &mut B, cx: i64, cy: i64, radius: u64, color: &Color) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let (width, height) = buf.dimensions();
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+
+    // Past the reach, the circle covers the whole buffer anyway.
+    let radius = i128::from(radius).min(circle_reach(buf, cx, cy));
+    let (cx, cy) = (i128::from(cx), i128::from(cy));
+    for y in (cy - radius).max(0)..=(cy + radius).min(i128::from(height) - 1) {
+        let dy = y - cy;
+        let half_width = ((radius - dy).saturating_mul(radius + dy) as f64).sqrt() as i128;
+        for x in (cx - half_width).max(0)..=(cx + half_width).min(i128::from(width) - 1) {
+            buf.set_pixel(x as u64, y as u64, color)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns a distance from `(cx, cy)` that is at least as far as every pixel of `buf`.
+fn circle_reach<B>(buf: &B, cx: i64, cy: i64) -> i128
+where
+    B: ColorBuf,
+{
+    let farthest = |center: i64, len: u64| {
+        let center = i128::from(center);
+        center.abs().max((center - i128::from(len) + 1).abs())
+    };
+    farthest(cx, buf.get_width()) + farthest(cy, buf.get_height())
+}
+
+/// Sets the pixel at `(x, y)` to `color`, unless it lies outside of `buf`.
+fn plot<B>(buf: &mut B, x: i128, y: i128, color: &Color) -> Result<()>
+where
+    B: ColorBuf,
+{
+    match (u64::try_from(x), u64::try_from(y)) {
+        (Ok(x), Ok(y)) if buf.in_bounds(x, y) => buf.set_pixel(x, y, color),
+        _ => Ok(()),
+    }
+}
+
+/// Fills the region of `buf` connected to `(x, y)` with `fill`.
+///
+/// The region consists of the pixels reachable from `(x, y)` through horizontally or vertically
+/// adjacent pixels, whose colors are within `tolerance` of the color at `(x, y)`. See
+/// [`Color::distance`].
+///
+/// [`Color::distance`]: ../struct.Color.html#method.distance
+pub fn flood_fill<B>(buf: &mut B, x: u64, y: u64, fill: &Color, tolerance: f32) -> Result<()>
+where
+    B: ColorBuf,
+{
+    let target = buf.get_pixel(x, y)?;
+    let (width, height) = buf.dimensions();
+
+    // Keep track of the visited pixels, since `fill` itself may be within the tolerance.
+    let mut visited = vec![false; buffer_len(buf)?];
+    let mut work = vec![(x, y)];
+    while let Some((x, y)) = work.pop() {
+        let index = (y * width + x) as usize;
+        if visited[index] {
+            continue;
+        }
+        visited[index] = true;
+        if buf.get_pixel(x, y)?.distance(&target) > tolerance {
+            continue;
+        }
+        buf.set_pixel(x, y, fill)?;
+
+        if x > 0 {
+            work.push((x - 1, y));
+        }
+        if x + 1 < width {
+            work.push((x + 1, y));
+        }
+        if y > 0 {
+            work.push((x, y - 1));
+        }
+        if y + 1 < height {
+            work.push((x, y + 1));
+        }
+    }
+    Ok(())
+}
+
+/// Replaces every pixel of `buf` within `tolerance` of `target` with `replacement`.
+///
+/// The distance between colors is measured with [`Color::distance`].
+///
+/// [`Color::distance`]: ../struct.Color.html#method.distance
+pub fn replace_color<B>(
+    buf: &mut B,
+    target: &Color,
+    replacement: &Color,
+    tolerance: f32,
+) -> Result<()>
+where
+    B: ColorBuf,
+{
+    map_pixels(buf, |color| {
+        if color.distance(target) <= tolerance {
+            *replacement
+        } else {
+            color
+        }
+    })
+}
+
+/// Checks whether `a` and `b` have the same dimensions and exactly the same pixels.
+pub fn buffers_equal<A, B>(a: &A, b: &B) -> bool
+where
+    A: ColorBuf,
+    B: ColorBuf,
+{
+    buffers_match(a, b, |a, b| a == b)
+}
+
+/// Checks whether `a` and `b` have the same dimensions and pixels, allowing every channel to
+/// differ by at most `epsilon`. See [`Color::approx_eq`].
+///
+/// [`Color::approx_eq`]: ../struct.Color.html#method.approx_eq
+pub fn buffers_approx_equal<A, B>(a: &A, b: &B, epsilon: f32) -> bool
+where
+    A: ColorBuf,
+    B: ColorBuf,
+{
+    buffers_match(a, b, |a, b| a.approx_eq(&b, epsilon))
+}
+
+/// Checks whether `a` and `b` have the same dimensions and every pair of pixels matches.
+fn buffers_match<A, B, F>(a: &A, b: &B, matches: F) -> bool
+where
+    A: ColorBuf,
+    B: ColorBuf,
+    F: Fn(Color, Color) -> bool,
+{
+    if a.dimensions() != b.dimensions() {
+        return false;
+    }
+    for y in 0..a.get_height() {
+        for x in 0..a.get_width() {
+            match (a.get_pixel(x, y), b.get_pixel(x, y)) {
+                (Ok(a), Ok(b)) if matches(a, b) => {}
+                _ => return false,
+            }
+        }
+    }
+    true
+}
+
+/// Tells [`add_border`] what to put in the border.
+///
+/// [`add_border`]: fn.add_border.html
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorderMode {
+    /// The border is filled with the given color.
+    Constant(Color),
+    /// The border repeats the nearest edge pixel.
+    Replicate,
+    /// The border continues from the opposite edge, as if the buffer was tiled.
+    Wrap,
+}
+
+/// Returns a copy of `src` surrounded by a border that is `size` pixels wide on every side.
+///
+/// The returned buffer is `width + 2 * size` pixels wide and `height + 2 * size` pixels high. The
+/// contents of the border are decided by `mode`. An empty `src` has nothing to replicate or wrap,
+/// so in those modes its border is transparent black.
+///
+/// Returns `ColorBufError::InvalidDimensions` if the returned buffer would be too large to
+/// allocate.
+pub fn add_border<B>(src: &B, size: u64, mode: BorderMode) -> Result<VecColorBuf>
+where
+    B: ColorBuf,
+{
+    let (width, height) = src.dimensions();
+    let grow = |len: u64| {
+        size.checked_mul(2)
+            .and_then(|border| len.checked_add(border))
+            .ok_or(ColorBufError::InvalidDimensions)
+    };
+    let (dst_width, dst_height) = (grow(width)?, grow(height)?);
+    dst_width
+        .checked_mul(dst_height)
+        .and_then(|len| usize::try_from(len).ok())
+        .ok_or(ColorBufError::InvalidDimensions)?;
+
+    let mut dst = VecColorBuf::new(dst_width, dst_height);
+    if width == 0 || height == 0 {
+        if let BorderMode::Constant(color) = mode {
+            dst.fill(&color)?;
+        }
+        return Ok(dst);
+    }
+
+    for y in 0..dst_height {
+        for x in 0..dst_width {
+            let src_x = i128::from(x) - i128::from(size);
+            let src_y = i128::from(y) - i128::from(size);
+            let inside = src_x >= 0 && src_y >= 0 && src.in_bounds(src_x as u64, src_y as u64);
+
+            let (src_x, src_y) = match mode {
+                _ if inside => (src_x, src_y),
+                BorderMode::Constant(color) => {
+                    dst.set_pixel(x, y, &color)?;
+                    continue;
+                }
+                BorderMode::Replicate => (
+                    src_x.clamp(0, i128::from(width) - 1),
+                    src_y.clamp(0, i128::from(height) - 1),
+                ),
+                BorderMode::Wrap => (
+                    src_x.rem_euclid(i128::from(width)),
+                    src_y.rem_euclid(i128::from(height)),
+                ),
+            };
+            let color = src.get_pixel(src_x as u64, src_y as u64)?;
+            dst.set_pixel(x, y, &color)?;
+        }
+    }
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a buffer where the red channel of every pixel is its `x` and green is its `y`.
+    fn coordinates(width: u64, height: u64) -> VecColorBuf {
+        let mut buf = VecColorBuf::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                buf.set_pixel(x, y, &coordinate(x, y)).unwrap();
+            }
+        }
+        buf
+    }
+
+    /// Returns a buffer of the given size whose pixels can't be read, since it has no data.
+    fn broken(width: u64, height: u64) -> BitmapColorBuf {
+        BitmapColorBuf::new(
+            ColorFormat::RGBA,
+            BitDepth::Eight,
+            height,
+            width,
+            4 * width,
+            Box::new([]),
+        )
+    }
+
+    fn coordinate(x: u64, y: u64) -> Color {
+        Color {
+            r: x as f32,
+            g: y as f32,
+            b: 0f32,
+            a: 1f32,
+        }
+    }
+
+    #[test]
+    fn full_size_subregion() {
+        let mut backing = VecColorBuf::new(100, 50);
+        let region = SubRegionColorBuf::new(&mut backing, 0, 0, 100, 50).unwrap();
+
+        assert_eq!(100, region.get_width());
+        assert_eq!(50, region.get_height());
+        assert!(region.get_pixel(99, 49).is_ok());
+    }
+
+    #[test]
+    fn bottom_right_subregion() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let mut backing = VecColorBuf::new(100, 50);
+        backing.set_pixel(99, 49, &red).unwrap();
+        let region = SubRegionColorBuf::new(&mut backing, 90, 40, 10, 10).unwrap();
+
+        assert_eq!(red, region.get_pixel(9, 9).unwrap());
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            region.get_pixel(10, 9)
+        );
+    }
+
+    #[test]
+    fn too_large_subregion() {
+        let mut backing = VecColorBuf::new(100, 50);
+
+        assert!(SubRegionColorBuf::new(&mut backing, 1, 0, 100, 50).is_err());
+        assert!(SubRegionColorBuf::new(&mut backing, 0, 1, 100, 50).is_err());
+    }
+
+    #[test]
+    fn overflowing_subregion() {
+        let mut backing = VecColorBuf::new(100, 50);
+
+        assert!(matches!(
+            SubRegionColorBuf::new(&mut backing, u64::MAX, 0, 10, 10),
+            Err(ColorBufError::InvalidDimensions)
+        ));
+        assert!(matches!(
+            SubRegionColorBuf::new(&mut backing, 0, u64::MAX - 1, 10, 10),
+            Err(ColorBufError::InvalidDimensions)
+        ));
+        assert!(matches!(
+            SubRegionColorBuf::new(&mut backing, 1, 1, u64::MAX, u64::MAX),
+            Err(ColorBufError::InvalidDimensions)
+        ));
+    }
+
+    #[test]
+    fn clamped_subregion() {
+        let mut backing = coordinates(10, 5);
+        let region = SubRegionColorBuf::new_clamped(&mut backing, 7, 1, 5, 3);
+
+        assert_eq!(3, region.get_width());
+        assert_eq!(3, region.get_height());
+        assert_eq!(coordinate(9, 3), region.get_pixel(2, 2).unwrap());
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            region.get_pixel(3, 0)
+        );
+    }
+
+    #[test]
+    fn clamped_subregion_outside() {
+        let mut backing = VecColorBuf::new(10, 5);
+        let region = SubRegionColorBuf::new_clamped(&mut backing, 12, 6, 5, 3);
+
+        assert_eq!((0, 0), region.dimensions());
+    }
+
+    #[test]
+    fn nested_subregion() {
+        let mut backing = coordinates(10, 10);
+        let mut outer = SubRegionColorBuf::new(&mut backing, 2, 3, 6, 6).unwrap();
+        let mut inner = outer.subregion(1, 2, 3, 3).unwrap();
+
+        assert_eq!(coordinate(5, 7), inner.get_pixel(2, 2).unwrap());
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), inner.get_pixel(3, 0));
+        inner.set_pixel(0, 0, &Color::WHITE).unwrap();
+
+        assert_eq!(Color::WHITE, outer.get_pixel(1, 2).unwrap());
+        assert!(outer.subregion(4, 0, 3, 1).is_err());
+        assert_eq!(Color::WHITE, backing.get_pixel(3, 5).unwrap());
+    }
+
+    #[test]
+    fn fill_rect_in_bounds() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let mut buf = VecColorBuf::new(10, 10);
+        fill_rect(&mut buf, 2, 3, 4, 5, &red).unwrap();
+
+        assert_eq!(red, buf.get_pixel(2, 3).unwrap());
+        assert_eq!(red, buf.get_pixel(5, 7).unwrap());
+        assert_ne!(red, buf.get_pixel(1, 3).unwrap());
+        assert_ne!(red, buf.get_pixel(6, 7).unwrap());
+        assert_ne!(red, buf.get_pixel(5, 8).unwrap());
+    }
+
+    #[test]
+    fn fill_rect_past_edge() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let mut buf = VecColorBuf::new(10, 10);
+
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            fill_rect(&mut buf, 8, 0, 4, 4, &red)
+        );
+        assert_eq!(VecColorBuf::new(10, 10), buf);
+    }
+
+    #[test]
+    fn blit_half_transparent() {
+        let white = Color {
+            r: 1f32,
+            g: 1f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let mut dst = VecColorBuf::filled(4, 4, white);
+        let src = VecColorBuf::filled(
+            2,
+            2,
+            Color {
+                r: 1f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0.5f32,
+            },
+        );
+        blit(&mut dst, &src, 1, 1).unwrap();
+
+        let blended = dst.get_pixel(2, 2).unwrap();
+        let expected = 0.5f32.powf(1f32 / 2.2f32);
+        assert!((blended.r - 1f32).abs() < 1e-6);
+        assert!((blended.g - expected).abs() < 1e-6);
+        assert!((blended.b - expected).abs() < 1e-6);
+        assert!((blended.a - 1f32).abs() < 1e-6);
+        assert_eq!(white, dst.get_pixel(0, 0).unwrap());
+        assert_eq!(white, dst.get_pixel(3, 3).unwrap());
+    }
+
+    #[test]
+    fn blit_clipped() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let mut dst = VecColorBuf::new(4, 4);
+        let src = VecColorBuf::filled(3, 3, red);
+        blit(&mut dst, &src, 2, 3).unwrap();
+        blit(&mut dst, &src, 10, 10).unwrap();
+
+        assert_eq!(red, dst.get_pixel(3, 3).unwrap());
+        assert_eq!(red, dst.get_pixel(2, 3).unwrap());
+        assert_ne!(red, dst.get_pixel(2, 2).unwrap());
+    }
+
+    #[test]
+    fn flip_horizontal_odd() {
+        let mut buf = coordinates(3, 3);
+        flip_horizontal(&mut buf).unwrap();
+
+        assert_eq!(coordinate(2, 0), buf.get_pixel(0, 0).unwrap());
+        assert_eq!(coordinate(0, 0), buf.get_pixel(2, 0).unwrap());
+        assert_eq!(coordinate(2, 2), buf.get_pixel(0, 2).unwrap());
+        assert_eq!(coordinate(0, 2), buf.get_pixel(2, 2).unwrap());
+        assert_eq!(coordinate(1, 1), buf.get_pixel(1, 1).unwrap());
+    }
+
+    #[test]
+    fn flip_vertical_odd() {
+        let mut buf = coordinates(3, 3);
+        flip_vertical(&mut buf).unwrap();
+
+        assert_eq!(coordinate(0, 2), buf.get_pixel(0, 0).unwrap());
+        assert_eq!(coordinate(2, 2), buf.get_pixel(2, 0).unwrap());
+        assert_eq!(coordinate(0, 0), buf.get_pixel(0, 2).unwrap());
+        assert_eq!(coordinate(2, 0), buf.get_pixel(2, 2).unwrap());
+        assert_eq!(coordinate(1, 1), buf.get_pixel(1, 1).unwrap());
+    }
+
+    #[test]
+    fn rotate() {
+        let buf = coordinates(2, 3);
+
+        let rotated = rotate90(&buf).unwrap();
+        assert_eq!(3, rotated.get_width());
+        assert_eq!(2, rotated.get_height());
+        assert_eq!(coordinate(0, 2), rotated.get_pixel(0, 0).unwrap());
+        assert_eq!(coordinate(0, 0), rotated.get_pixel(2, 0).unwrap());
+        assert_eq!(coordinate(1, 2), rotated.get_pixel(0, 1).unwrap());
+
+        let rotated = rotate180(&buf).unwrap();
+        assert_eq!(2, rotated.get_width());
+        assert_eq!(3, rotated.get_height());
+        assert_eq!(coordinate(1, 2), rotated.get_pixel(0, 0).unwrap());
+        assert_eq!(coordinate(0, 0), rotated.get_pixel(1, 2).unwrap());
+
+        let rotated = rotate270(&buf).unwrap();
+        assert_eq!(3, rotated.get_width());
+        assert_eq!(2, rotated.get_height());
+        assert_eq!(coordinate(1, 0), rotated.get_pixel(0, 0).unwrap());
+        assert_eq!(coordinate(0, 2), rotated.get_pixel(2, 1).unwrap());
+
+        let broken = broken(2, 3);
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), rotate90(&broken));
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), rotate180(&broken));
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), rotate270(&broken));
+    }
+
+    #[test]
+    fn resize_nearest_checkerboard() {
+        let buf = coordinates(2, 2);
+
+        let scaled = resize_nearest(&buf, 4, 4).unwrap();
+        assert_eq!(4, scaled.get_width());
+        assert_eq!(4, scaled.get_height());
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(coordinate(x / 2, y / 2), scaled.get_pixel(x, y).unwrap());
+            }
+        }
+
+        let scaled = resize_nearest(&buf, 1, 1).unwrap();
+        assert_eq!(1, scaled.get_width());
+        assert_eq!(1, scaled.get_height());
+        assert_eq!(coordinate(1, 1), scaled.get_pixel(0, 0).unwrap());
+    }
+
+    #[test]
+    fn resize_nearest_empty() {
+        let buf = coordinates(2, 2);
+
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            resize_nearest(&buf, 0, 4)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            resize_nearest(&buf, 4, 0)
+        );
+    }
+
+    #[test]
+    fn resize_bilinear_gradient() {
+        let mut buf = VecColorBuf::new(2, 1);
+        buf.set_pixel(
+            0,
+            0,
+            &Color {
+                r: 0f32,
+                g: 0f32,
+                b: 0f32,
+                a: 1f32,
+            },
+        )
+        .unwrap();
+        buf.set_pixel(
+            1,
+            0,
+            &Color {
+                r: 1f32,
+                g: 1f32,
+                b: 1f32,
+                a: 1f32,
+            },
+        )
+        .unwrap();
+
+        let scaled = resize_bilinear(&buf, 4, 1).unwrap();
+        assert_eq!(4, scaled.get_width());
+        assert_eq!(1, scaled.get_height());
+
+        let values: Vec<f32> = (0..4).map(|x| scaled.get_pixel(x, 0).unwrap().r).collect();
+        assert_eq!(0f32, values[0]);
+        assert_eq!(1f32, values[3]);
+        assert!(values[0] < values[1]);
+        assert!(values[1] < values[2]);
+        assert!(values[2] < values[3]);
+    }
+
+    #[test]
+    fn crop_center() {
+        let buf = coordinates(5, 4);
+
+        let cropped = crop(&buf, 1, 1, 3, 2).unwrap();
+        assert_eq!(3, cropped.get_width());
+        assert_eq!(2, cropped.get_height());
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(coordinate(x + 1, y + 1), cropped.get_pixel(x, y).unwrap());
+            }
+        }
+
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            crop(&buf, 3, 0, 3, 2)
+        );
+    }
+
+    #[test]
+    fn iter_pixels_order() {
+        let buf = coordinates(2, 2);
+        let pixels: Vec<(u64, u64, Color)> = iter_pixels(&buf).map(Result::unwrap).collect();
+
+        assert_eq!(
+            vec![
+                (0, 0, coordinate(0, 0)),
+                (1, 0, coordinate(1, 0)),
+                (0, 1, coordinate(0, 1)),
+                (1, 1, coordinate(1, 1)),
+            ],
+            pixels
+        );
+    }
+
+    #[test]
+    fn iter_rows_order() {
+        let buf = coordinates(2, 2);
+        let rows: Vec<Vec<Color>> = iter_rows(&buf).map(Result::unwrap).collect();
+
+        assert_eq!(
+            vec![
+                vec![coordinate(0, 0), coordinate(1, 0)],
+                vec![coordinate(0, 1), coordinate(1, 1)],
+            ],
+            rows
+        );
+    }
+
+    #[test]
+    fn map_pixels_invert() {
+        let mut buf = coordinates(2, 2);
+        map_pixels(&mut buf, |color| Color {
+            r: 1f32 - color.r,
+            g: 1f32 - color.g,
+            b: 1f32 - color.b,
+            a: color.a,
+        })
+        .unwrap();
+
+        assert_eq!(
+            Color {
+                r: 1f32,
+                g: 1f32,
+                b: 1f32,
+                a: 1f32,
+            },
+            buf.get_pixel(0, 0).unwrap()
+        );
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 1f32,
+                b: 1f32,
+                a: 1f32,
+            },
+            buf.get_pixel(1, 0).unwrap()
+        );
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 1f32,
+                a: 1f32,
+            },
+            buf.get_pixel(1, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn map_pixels_call_count() {
+        let calls = std::cell::Cell::new(0);
+        let mut buf = coordinates(3, 5);
+        map_pixels(&mut buf, |color| {
+            calls.set(calls.get() + 1);
+            color
+        })
+        .unwrap();
+
+        assert_eq!(15, calls.get());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_map_pixels_matches_serial() {
+        let f = |color: Color| Color {
+            r: color.g * 0.5f32,
+            g: color.r + 1f32,
+            b: color.r * color.g,
+            a: color.a,
+        };
+        let mut serial = coordinates(37, 23);
+        let mut parallel = coordinates(37, 23);
+        map_pixels(&mut serial, f).unwrap();
+        par_map_pixels(&mut parallel, f);
+
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn rotate_hue_red_to_green() {
+        let mut buf = VecColorBuf::filled(
+            1,
+            1,
+            Color {
+                r: 1f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0.5f32,
+            },
+        );
+        rotate_hue(&mut buf, 120f32).unwrap();
+
+        let color = buf.get_pixel(0, 0).unwrap();
+        assert!(color.r.abs() < 1e-6);
+        assert!((color.g - 1f32).abs() < 1e-6);
+        assert!(color.b.abs() < 1e-6);
+        assert_eq!(0.5f32, color.a);
+    }
+
+    #[test]
+    fn adjust_brightness_contrast_identity() {
+        let color = Color {
+            r: 0.2f32,
+            g: 0.5f32,
+            b: 0.8f32,
+            a: 0.3f32,
+        };
+        let mut buf = VecColorBuf::filled(2, 2, color);
+        adjust_brightness_contrast(&mut buf, 0f32, 1f32).unwrap();
+
+        let adjusted = buf.get_pixel(1, 1).unwrap();
+        assert!((adjusted.r - color.r).abs() < 1e-6);
+        assert!((adjusted.g - color.g).abs() < 1e-6);
+        assert!((adjusted.b - color.b).abs() < 1e-6);
+        assert_eq!(color.a, adjusted.a);
+    }
+
+    #[test]
+    fn adjust_brightness_contrast_extreme() {
+        let mut buf = VecColorBuf::filled(
+            2,
+            1,
+            Color {
+                r: 0.45f32,
+                g: 0.5f32,
+                b: 0.55f32,
+                a: 1f32,
+            },
+        );
+        adjust_brightness_contrast(&mut buf, 0f32, 100f32).unwrap();
+
+        let adjusted = buf.get_pixel(0, 0).unwrap();
+        assert_eq!(0f32, adjusted.r);
+        assert_eq!(0.5f32, adjusted.g);
+        assert_eq!(1f32, adjusted.b);
+        assert_eq!(1f32, adjusted.a);
+    }
+
+    #[test]
+    fn adjust_saturation_zero_is_grayscale() {
+        let color = Color {
+            r: 0.2f32,
+            g: 0.5f32,
+            b: 0.8f32,
+            a: 0.3f32,
+        };
+        let mut buf = VecColorBuf::filled(2, 2, color);
+        adjust_saturation(&mut buf, 0f32).unwrap();
+
+        let adjusted = buf.get_pixel(1, 0).unwrap();
+        let gray = color.to_grayscale();
+        assert!((adjusted.r - gray.r).abs() < 1e-6);
+        assert!((adjusted.g - gray.g).abs() < 1e-6);
+        assert!((adjusted.b - gray.b).abs() < 1e-6);
+        assert_eq!(color.a, adjusted.a);
+    }
+
+    #[test]
+    fn adjust_saturation_one_is_identity() {
+        let mut buf = coordinates(2, 2);
+        adjust_saturation(&mut buf, 1f32).unwrap();
+
+        assert!(buffers_approx_equal(&coordinates(2, 2), &buf, 1e-6));
+    }
+
+    #[test]
+    fn fill_gradient() {
+        let black = Color {
+            r: 0f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let white = Color {
+            r: 1f32,
+            g: 1f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let gray = Color {
+            r: 0.5f32,
+            g: 0.5f32,
+            b: 0.5f32,
+            a: 1f32,
+        };
+
+        let mut buf = VecColorBuf::new(3, 1);
+        fill_gradient_horizontal(&mut buf, black, white).unwrap();
+        assert_eq!(black, buf.get_pixel(0, 0).unwrap());
+        assert_eq!(gray, buf.get_pixel(1, 0).unwrap());
+        assert_eq!(white, buf.get_pixel(2, 0).unwrap());
+
+        let mut buf = VecColorBuf::new(1, 3);
+        fill_gradient_vertical(&mut buf, black, white).unwrap();
+        assert_eq!(black, buf.get_pixel(0, 0).unwrap());
+        assert_eq!(gray, buf.get_pixel(0, 1).unwrap());
+        assert_eq!(white, buf.get_pixel(0, 2).unwrap());
+    }
+
+    #[test]
+    fn overlay_color_half_red() {
+        let red = Color {
+            a: 0.5f32,
+            ..Color::RED
+        };
+        let mut buf = VecColorBuf::filled(2, 2, Color::WHITE);
+        overlay_color(&mut buf, &red, 2.2f32).unwrap();
+
+        assert_eq!(
+            Color::WHITE.blend_with_gamma(red, 2.2f32),
+            buf.get_pixel(1, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn tiled_wraps() {
+        let mut pattern = coordinates(2, 3);
+        let mut tiled = TiledColorBuf::new(&mut pattern, 10, 10).unwrap();
+
+        assert_eq!(10, tiled.get_width());
+        assert_eq!(10, tiled.get_height());
+        assert_eq!(coordinate(1, 2), tiled.get_pixel(1, 2).unwrap());
+        assert_eq!(coordinate(0, 0), tiled.get_pixel(2, 3).unwrap());
+        assert_eq!(coordinate(1, 1), tiled.get_pixel(9, 7).unwrap());
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            tiled.get_pixel(10, 0)
+        );
+
+        tiled.set_pixel(5, 5, &Color::RED).unwrap();
+        assert_eq!(Color::RED, tiled.get_pixel(1, 2).unwrap());
+        assert_eq!(Color::RED, pattern.get_pixel(1, 2).unwrap());
+    }
+
+    #[test]
+    fn clamped_edges() {
+        let buf = coordinates(3, 2);
+        let mut clamped = ClampedColorBuf::new(&buf).unwrap();
+
+        assert_eq!(coordinate(1, 1), clamped.get_pixel(1, 1).unwrap());
+        assert_eq!(
+            coordinate(0, 0),
+            clamped
+                .get_pixel(0u64.wrapping_sub(1), 0u64.wrapping_sub(5))
+                .unwrap()
+        );
+        assert_eq!(coordinate(2, 1), clamped.get_pixel(3, 2).unwrap());
+        assert_eq!(coordinate(2, 0), clamped.get_pixel(100, 0).unwrap());
+        assert_eq!(
+            Err(ColorBufError::ReadOnly),
+            clamped.set_pixel(0, 0, &Color::RED)
+        );
+    }
+
+    #[test]
+    fn box_blur_spread() {
+        let mut buf = VecColorBuf::filled(5, 5, Color::BLACK);
+        buf.set_pixel(2, 2, &Color::WHITE).unwrap();
+
+        let blurred = box_blur(&buf, 1).unwrap();
+        for y in 0..5 {
+            for x in 0..5 {
+                let color = blurred.get_pixel(x, y).unwrap();
+                let expected = if (1..=3).contains(&x) && (1..=3).contains(&y) {
+                    1f32 / 9f32
+                } else {
+                    0f32
+                };
+                assert!((color.r - expected).abs() < 1e-6);
+                assert!((color.a - 1f32).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn box_blur_edges() {
+        let mut buf = VecColorBuf::filled(3, 1, Color::BLACK);
+        buf.set_pixel(0, 0, &Color::WHITE).unwrap();
+
+        // The left edge is extended, so the first pixel sees two white pixels out of three.
+        let blurred = box_blur(&buf, 1).unwrap();
+        assert!((blurred.get_pixel(0, 0).unwrap().r - 2f32 / 3f32).abs() < 1e-6);
+        assert!((blurred.get_pixel(1, 0).unwrap().r - 1f32 / 3f32).abs() < 1e-6);
+        assert!((blurred.get_pixel(2, 0).unwrap().r - 0f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn box_blur_huge_radius() {
+        let mut buf = VecColorBuf::filled(3, 2, Color::BLACK);
+        buf.set_pixel(0, 0, &Color::WHITE).unwrap();
+
+        // Only the edge pixels matter once the window is much wider than the buffer.
+        let blurred = box_blur(&buf, 1 << 40).unwrap();
+        for pixel in iter_pixels(&blurred) {
+            let (_, _, color) = pixel.unwrap();
+            assert!((color.r - 0.25f32).abs() < 1e-6);
+        }
+
+        let blurred = box_blur(&buf, u64::MAX).unwrap();
+        assert!((blurred.get_pixel(2, 1).unwrap().r - 0.25f32).abs() < 1e-6);
+
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            box_blur(&broken(3, 2), 1)
+        );
+    }
+
+    #[test]
+    fn convolve3x3_identity() {
+        let mut buf = VecColorBuf::new(3, 2);
+        fill_gradient_horizontal(
+            &mut buf,
+            Color::RED,
+            Color {
+                a: 0.5f32,
+                ..Color::BLUE
+            },
+        )
+        .unwrap();
+
+        let identity = [[0f32, 0f32, 0f32], [0f32, 1f32, 0f32], [0f32, 0f32, 0f32]];
+        assert_eq!(buf, convolve3x3(&buf, identity, 1f32, 0f32).unwrap());
+    }
+
+    #[test]
+    fn convolve3x3_edges() {
+        let buf = VecColorBuf::filled(2, 2, Color::WHITE);
+
+        // Every neighbour is white thanks to edge clamping, so the sum is 9 everywhere.
+        let blurred = convolve3x3(&buf, [[1f32; 3]; 3], 9f32, 0f32).unwrap();
+        assert_eq!(buf, blurred);
+
+        let darkened = convolve3x3(&buf, [[1f32; 3]; 3], 18f32, -0.25f32).unwrap();
+        assert_eq!(
+            Color {
+                r: 0.25f32,
+                g: 0.25f32,
+                b: 0.25f32,
+                a: 1f32,
+            },
+            darkened.get_pixel(1, 1).unwrap()
+        );
+
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            convolve3x3(&broken(2, 2), [[1f32; 3]; 3], 9f32, 0f32)
+        );
+    }
+
+    #[test]
+    fn invert_buffer() {
+        let mut buf = VecColorBuf::filled(2, 2, Color::WHITE);
+        buf.set_pixel(1, 1, &Color::TRANSPARENT).unwrap();
+        invert(&mut buf).unwrap();
+
+        assert_eq!(Color::BLACK, buf.get_pixel(0, 0).unwrap());
+        assert_eq!(
+            Color {
+                a: 0f32,
+                ..Color::WHITE
+            },
+            buf.get_pixel(1, 1).unwrap()
+        );
+
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            convolve3x3(&broken(2, 2), [[1f32; 3]; 3], 9f32, 0f32)
+        );
+    }
+
+    #[test]
+    fn threshold_gradient() {
+        let mut buf = VecColorBuf::new(5, 1);
+        fill_gradient_horizontal(&mut buf, Color::BLACK, Color::WHITE).unwrap();
+        threshold(&mut buf, 0.6f32).unwrap();
+
+        assert_eq!(Color::BLACK, buf.get_pixel(0, 0).unwrap());
+        assert_eq!(Color::BLACK, buf.get_pixel(1, 0).unwrap());
+        assert_eq!(Color::BLACK, buf.get_pixel(2, 0).unwrap());
+        assert_eq!(Color::WHITE, buf.get_pixel(3, 0).unwrap());
+        assert_eq!(Color::WHITE, buf.get_pixel(4, 0).unwrap());
+    }
+
+    #[test]
+    fn apply_lut_identity() {
+        let mut lut = [0f32; 256];
+        for (value, entry) in lut.iter_mut().enumerate() {
+            *entry = value as f32 / 255f32;
+        }
+        let color = Color::from_rgba8(10, 128, 255, 100);
+        let mut buf = VecColorBuf::filled(2, 2, color);
+        apply_lut(&mut buf, &lut).unwrap();
+
+        assert_eq!(VecColorBuf::filled(2, 2, color), buf);
+    }
+
+    #[test]
+    fn apply_lut_invert() {
+        let mut lut = [0f32; 256];
+        for (value, entry) in lut.iter_mut().enumerate() {
+            *entry = 1f32 - value as f32 / 255f32;
+        }
+        let mut buf = VecColorBuf::filled(1, 1, Color::from_rgba8(0, 51, 255, 100));
+        apply_lut(&mut buf, &lut).unwrap();
+
+        assert!(buf
+            .get_pixel(0, 0)
+            .unwrap()
+            .approx_eq(&Color::from_rgba8(255, 204, 0, 100), 1e-6f32));
+    }
+
+    #[test]
+    fn lut3d_invalid() {
+        assert_eq!(Err(ColorBufError::InvalidDimensions), Lut3d::identity(0));
+        assert_eq!(Err(ColorBufError::InvalidDimensions), Lut3d::identity(1));
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            Lut3d::identity(usize::MAX)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            Lut3d::new(2, vec![[0f32; 3]; 7])
+        );
+        assert_eq!(17, Lut3d::identity(17).unwrap().size());
+    }
+
+    #[test]
+    fn apply_lut3d_identity() {
+        let mut buf = VecColorBuf::new(3, 1);
+        let colors = [
+            Color {
+                r: 0.1f32,
+                g: 0.5f32,
+                b: 0.9f32,
+                a: 0.3f32,
+            },
+            Color::WHITE,
+            Color {
+                r: 0.33f32,
+                g: 0.02f32,
+                b: 0.71f32,
+                a: 1f32,
+            },
+        ];
+        for (x, color) in colors.iter().enumerate() {
+            buf.set_pixel(x as u64, 0, color).unwrap();
+        }
+        let original = buf.clone();
+        apply_lut3d(&mut buf, &Lut3d::identity(17).unwrap()).unwrap();
+
+        assert!(buffers_approx_equal(&original, &buf, 1e-5f32));
+    }
+
+    #[test]
+    fn apply_lut3d_swap_red_blue() {
+        let mut data = Vec::new();
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    data.push([b as f32, g as f32, r as f32]);
+                }
+            }
+        }
+        let lut = Lut3d::new(2, data).unwrap();
+        let mut buf = VecColorBuf::filled(
+            1,
+            1,
+            Color {
+                r: 0.25f32,
+                g: 0.5f32,
+                b: 0.75f32,
+                a: 0.5f32,
+            },
+        );
+        apply_lut3d(&mut buf, &lut).unwrap();
+
+        assert!(buf.get_pixel(0, 0).unwrap().approx_eq(
+            &Color {
+                r: 0.75f32,
+                g: 0.5f32,
+                b: 0.25f32,
+                a: 0.5f32,
+            },
+            1e-6f32
+        ));
+    }
+
+    #[test]
+    fn posterize_two_levels() {
+        let mut buf = VecColorBuf::new(2, 1);
+        buf.set_pixel(
+            0,
+            0,
+            &Color {
+                r: 0.2f32,
+                g: 0.49f32,
+                b: 0.51f32,
+                a: 0.3f32,
+            },
+        )
+        .unwrap();
+        buf.set_pixel(
+            1,
+            0,
+            &Color {
+                r: 0.9f32,
+                g: 0.6f32,
+                b: 0.1f32,
+                a: 0.7f32,
+            },
+        )
+        .unwrap();
+        posterize(&mut buf, 2).unwrap();
+
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 1f32,
+                a: 0.3f32,
+            },
+            buf.get_pixel(0, 0).unwrap()
+        );
+        assert_eq!(
+            Color {
+                r: 1f32,
+                g: 1f32,
+                b: 0f32,
+                a: 0.7f32,
+            },
+            buf.get_pixel(1, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn posterize_too_few_levels() {
+        let mut buf = coordinates(2, 2);
+        posterize(&mut buf, 1).unwrap();
+
+        assert_eq!(coordinates(2, 2), buf);
+    }
+
+    #[test]
+    fn dither_floyd_steinberg_gradient() {
+        let mut buf = VecColorBuf::new(16, 4);
+        fill_gradient_horizontal(&mut buf, Color::BLACK, Color::WHITE).unwrap();
+        let mut posterized = buf.clone();
+        posterize(&mut posterized, 2).unwrap();
+        dither_floyd_steinberg(&mut buf, 2).unwrap();
+
+        for pixel in iter_pixels(&buf) {
+            let (_, _, color) = pixel.unwrap();
+            for channel in [color.r, color.g, color.b].iter() {
+                assert!(*channel == 0f32 || *channel == 1f32);
+            }
+            assert_eq!(1f32, color.a);
+        }
+        assert!(!pixels_of(&buf, Color::BLACK).is_empty());
+        assert!(!pixels_of(&buf, Color::WHITE).is_empty());
+        assert_ne!(posterized, buf);
+    }
+
+    #[test]
+    fn convert_format_rgb_rgba() {
+        let rgb = BitmapColorBuf::new(
+            ColorFormat::RGB,
+            BitDepth::Eight,
+            1,
+            2,
+            6,
+            Box::new([0xFF, 0x80, 0x00, 0x10, 0x20, 0x30]),
+        );
+        let rgba = convert_format(&rgb, ColorFormat::RGBA, BitDepth::Eight).unwrap();
+
+        assert_eq!(
+            &[0xFF, 0x80, 0x00, 0xFF, 0x10, 0x20, 0x30, 0xFF],
+            rgba.row_bytes(0).unwrap()
+        );
+        assert_eq!(1f32, rgba.get_pixel(1, 0).unwrap().a);
+
+        let back = convert_format(&rgba, ColorFormat::RGB, BitDepth::Eight).unwrap();
+        assert_eq!(rgb.row_bytes(0).unwrap(), back.row_bytes(0).unwrap());
+
+        assert_eq!(
+            Some(ColorBufError::InvalidCoordinate),
+            convert_format(&broken(2, 1), ColorFormat::RGB, BitDepth::Eight).err()
+        );
+    }
+
+    #[test]
+    fn convert_format_translucent_to_rgb() {
+        let mut rgba = VecColorBuf::filled(
+            2,
+            1,
+            Color {
+                r: 0.4f32,
+                g: 0.2f32,
+                b: 0f32,
+                a: 0.5f32,
+            },
+        );
+        rgba.set_pixel(1, 0, &Color::TRANSPARENT).unwrap();
+        let rgb = convert_format(&rgba, ColorFormat::RGB, BitDepth::Eight).unwrap();
+
+        assert_eq!(&[204, 102, 0, 0, 0, 0], rgb.row_bytes(0).unwrap());
+    }
+
+    #[test]
+    fn extract_alpha_half_transparent() {
+        let half = Color {
+            r: 0.2f32,
+            g: 0.4f32,
+            b: 0.6f32,
+            a: 0.5f32,
+        };
+        let mut buf = VecColorBuf::filled(2, 2, half);
+        buf.set_pixel(1, 1, &Color::TRANSPARENT).unwrap();
+        let alpha = extract_alpha(&buf).unwrap();
+
+        assert_eq!(
+            Color {
+                r: 0.5f32,
+                g: 0.5f32,
+                b: 0.5f32,
+                a: 1f32,
+            },
+            alpha.get_pixel(0, 1).unwrap()
+        );
+        assert_eq!(Color::BLACK, alpha.get_pixel(1, 1).unwrap());
+    }
+
+    #[test]
+    fn extract_channel_green() {
+        let buf = coordinates(2, 3);
+        let green = extract_channel(&buf, Channel::G).unwrap();
+
+        assert_eq!((2, 3), green.dimensions());
+        assert_eq!(Color::WHITE, green.get_pixel(0, 1).unwrap());
+        assert_eq!(Color::BLACK, green.get_pixel(1, 0).unwrap());
+    }
+
+    #[test]
+    fn histogram_white() {
+        let buf = VecColorBuf::filled(3, 2, Color::WHITE);
+        let histogram = histogram(&buf, 4).unwrap();
+
+        for channel in histogram.iter() {
+            assert_eq!(vec![0, 0, 0, 6], *channel);
+        }
+    }
+
+    #[test]
+    fn histogram_mixed() {
+        let mut buf = VecColorBuf::filled(2, 1, Color::TRANSPARENT);
+        buf.set_pixel(1, 0, &Color::RED).unwrap();
+        let histogram = histogram(&buf, 2).unwrap();
+
+        assert_eq!(vec![1, 1], histogram[0]);
+        assert_eq!(vec![2, 0], histogram[1]);
+        assert_eq!(vec![2, 0], histogram[2]);
+        assert_eq!(vec![1, 1], histogram[3]);
+    }
+
+    #[test]
+    fn count_colors_two() {
+        let mut buf = VecColorBuf::filled(2, 2, Color::RED);
+        buf.set_pixel(0, 1, &Color::BLUE).unwrap();
+        buf.set_pixel(1, 1, &Color::from_rgba8(0, 0, 255, 255))
+            .unwrap();
+
+        assert_eq!(Ok(2), count_colors(&buf));
+        assert_eq!(Ok(0), count_colors(&VecColorBuf::new(0, 0)));
+    }
+
+    #[test]
+    fn quantize_palette_two_colors() {
+        let mut buf = VecColorBuf::filled(4, 4, Color::RED);
+        buf.set_pixel(3, 3, &Color::BLUE).unwrap();
+        let palette = quantize_palette(&buf, 16).unwrap();
+
+        assert_eq!(2, palette.len());
+        assert!(palette.contains(&Color::RED));
+        assert!(palette.contains(&Color::BLUE));
+    }
+
+    #[test]
+    fn quantize_palette_limit() {
+        let buf = coordinates(4, 4);
+        let palette = quantize_palette(&buf, 4).unwrap();
+
+        assert_eq!(4, palette.len());
+        assert!(palette.contains(&Color {
+            r: 0.5f32,
+            g: 0.5f32,
+            b: 0f32,
+            a: 1f32,
+        }));
+        assert_eq!(16, quantize_palette(&buf, 100).unwrap().len());
+        assert!(quantize_palette(&buf, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn statistics() {
+        let mut buf = VecColorBuf::filled(2, 1, Color::TRANSPARENT);
+        buf.set_pixel(1, 0, &Color::WHITE).unwrap();
+
+        assert_eq!(
+            Color {
+                r: 0.5f32,
+                g: 0.5f32,
+                b: 0.5f32,
+                a: 0.5f32,
+            },
+            average_color(&buf).unwrap()
+        );
+        assert_eq!(Ok((Color::TRANSPARENT, Color::WHITE)), bounds(&buf));
+    }
+
+    #[test]
+    fn statistics_empty() {
+        let buf = VecColorBuf::new(0, 0);
+
+        assert_eq!(Ok(Color::TRANSPARENT), average_color(&buf));
+        assert_eq!(Ok((Color::TRANSPARENT, Color::TRANSPARENT)), bounds(&buf));
+    }
+
+    #[test]
+    fn statistics_errors() {
+        let buf = broken(2, 2);
+
+        assert_eq!(
+            Some(ColorBufError::InvalidCoordinate),
+            iter_rows(&buf).next().unwrap().err()
+        );
+        assert_eq!(
+            Some(ColorBufError::InvalidCoordinate),
+            iter_pixels(&buf).next().unwrap().err()
+        );
+        assert_eq!(2, iter_pixels(&buf).count());
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), average_color(&buf));
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), bounds(&buf));
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), histogram(&buf, 4));
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), count_colors(&buf));
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            quantize_palette(&buf, 4)
+        );
+        assert_eq!(Err(ColorBufError::InvalidCoordinate), extract_alpha(&buf));
+    }
+
+    /// Returns the coordinates of every pixel of `buf` that is `color`.
+    fn pixels_of(buf: &VecColorBuf, color: Color) -> Vec<(u64, u64)> {
+        iter_pixels(buf)
+            .map(Result::unwrap)
+            .filter(|&(_, _, pixel)| pixel == color)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn draw_line_straight() {
+        let mut buf = VecColorBuf::new(5, 5);
+        draw_line(&mut buf, -2, 1, 3, 1, &Color::RED).unwrap();
+        draw_line(&mut buf, 4, 4, 4, 2, &Color::BLUE).unwrap();
+
+        assert_eq!(
+            vec![(0, 1), (1, 1), (2, 1), (3, 1)],
+            pixels_of(&buf, Color::RED)
+        );
+        assert_eq!(vec![(4, 2), (4, 3), (4, 4)], pixels_of(&buf, Color::BLUE));
+    }
+
+    #[test]
+    fn draw_line_diagonal() {
+        let mut buf = VecColorBuf::new(4, 4);
+        draw_line(&mut buf, 3, 3, -1, -1, &Color::RED).unwrap();
+
+        assert_eq!(
+            vec![(0, 0), (1, 1), (2, 2), (3, 3)],
+            pixels_of(&buf, Color::RED)
+        );
+    }
+
+    #[test]
+    fn draw_circle_outline() {
+        let mut buf = VecColorBuf::new(7, 7);
+        draw_circle(&mut buf, 3, 3, 2, &Color::RED).unwrap();
+
+        assert_eq!(Color::RED, buf.get_pixel(5, 3).unwrap());
+        assert_eq!(Color::RED, buf.get_pixel(1, 3).unwrap());
+        assert_eq!(Color::RED, buf.get_pixel(3, 5).unwrap());
+        assert_eq!(Color::RED, buf.get_pixel(3, 1).unwrap());
+        assert_ne!(Color::RED, buf.get_pixel(3, 3).unwrap());
+        assert_ne!(Color::RED, buf.get_pixel(6, 3).unwrap());
+    }
+
+    #[test]
+    fn fill_circle_disc() {
+        let mut buf = VecColorBuf::new(5, 5);
+        fill_circle(&mut buf, 2, 2, 1, &Color::RED).unwrap();
+
+        assert_eq!(
+            vec![(2, 1), (1, 2), (2, 2), (3, 2), (2, 3)],
+            pixels_of(&buf, Color::RED)
+        );
+
+        // Clipped circles are fine too.
+        fill_circle(&mut buf, 0, 0, 10, &Color::BLUE).unwrap();
+        assert_eq!(25, pixels_of(&buf, Color::BLUE).len());
+    }
+
+    #[test]
+    fn huge_circles() {
+        let mut buf = VecColorBuf::new(5, 5);
+        fill_circle(&mut buf, i64::MIN, i64::MAX, u64::MAX, &Color::RED).unwrap();
+        assert_eq!(25, pixels_of(&buf, Color::RED).len());
+
+        draw_circle(&mut buf, 2, 2, u64::MAX, &Color::BLUE).unwrap();
+        draw_circle(&mut buf, i64::MAX, i64::MIN, 3, &Color::BLUE).unwrap();
+        assert!(pixels_of(&buf, Color::BLUE).is_empty());
+    }
+
+    #[test]
+    fn draw_rect_border() {
+        let mut buf = VecColorBuf::new(6, 6);
+        draw_rect(&mut buf, 1, 1, 4, 5, &Color::RED, 1).unwrap();
+
+        let border = pixels_of(&buf, Color::RED);
+        assert_eq!(14, border.len());
+        assert!(border.contains(&(1, 1)));
+        assert!(border.contains(&(4, 5)));
+        assert!(!border.contains(&(2, 2)));
+        assert!(!border.contains(&(3, 4)));
+        assert!(!border.contains(&(0, 0)));
+    }
+
+    #[test]
+    fn draw_rect_thick() {
+        let mut buf = VecColorBuf::new(4, 4);
+        draw_rect(&mut buf, 0, 0, 4, 3, &Color::RED, 10).unwrap();
+
+        assert_eq!(12, pixels_of(&buf, Color::RED).len());
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            draw_rect(&mut buf, 1, 1, 4, 3, &Color::RED, 1)
+        );
+    }
+
+    #[test]
+    fn flood_fill_quadrant() {
+        // The top-left quadrant is white, and the rest is black.
+        let mut buf = VecColorBuf::filled(4, 4, Color::BLACK);
+        fill_rect(&mut buf, 0, 0, 2, 2, &Color::WHITE).unwrap();
+        buf.set_pixel(1, 1, &Color::from_rgba8(250, 250, 250, 255))
+            .unwrap();
+
+        flood_fill(&mut buf, 0, 1, &Color::RED, 0.1f32).unwrap();
+
+        assert_eq!(
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)],
+            pixels_of(&buf, Color::RED)
+        );
+        assert_eq!(12, pixels_of(&buf, Color::BLACK).len());
+    }
+
+    #[test]
+    fn flood_fill_similar_color() {
+        let mut buf = VecColorBuf::filled(3, 3, Color::WHITE);
+        flood_fill(&mut buf, 1, 1, &Color::WHITE, 0f32).unwrap();
+        flood_fill(
+            &mut buf,
+            1,
+            1,
+            &Color::from_rgba8(255, 255, 254, 255),
+            0.5f32,
+        )
+        .unwrap();
+
+        assert_eq!(
+            9,
+            pixels_of(&buf, Color::from_rgba8(255, 255, 254, 255)).len()
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            flood_fill(&mut buf, 3, 0, &Color::RED, 0f32)
+        );
+    }
+
+    #[test]
+    fn replace_near_white() {
+        let near_white = Color::from_rgba8(250, 252, 255, 255);
+        let mut buf = VecColorBuf::filled(3, 1, Color::WHITE);
+        buf.set_pixel(1, 0, &near_white).unwrap();
+        buf.set_pixel(2, 0, &Color::RED).unwrap();
+
+        replace_color(&mut buf, &Color::WHITE, &Color::TRANSPARENT, 0.05f32).unwrap();
+
+        assert_eq!(Color::TRANSPARENT, buf.get_pixel(0, 0).unwrap());
+        assert_eq!(Color::TRANSPARENT, buf.get_pixel(1, 0).unwrap());
+        assert_eq!(Color::RED, buf.get_pixel(2, 0).unwrap());
+    }
+
+    #[test]
+    fn compare_buffers() {
+        let a = coordinates(3, 2);
+        let mut b = coordinates(3, 2);
+
+        assert!(buffers_equal(&a, &b));
+        assert!(buffers_approx_equal(&a, &b, 0f32));
+
+        let mut color = b.get_pixel(2, 1).unwrap();
+        color.g += 0.001f32;
+        b.set_pixel(2, 1, &color).unwrap();
+        assert!(!buffers_equal(&a, &b));
+        assert!(buffers_approx_equal(&a, &b, 0.01f32));
+        assert!(!buffers_approx_equal(&a, &b, 0.0001f32));
+
+        let c = coordinates(2, 3);
+        assert!(!buffers_equal(&a, &c));
+        assert!(!buffers_approx_equal(&a, &c, 1f32));
+    }
+
+    #[test]
+    fn add_border_constant() {
+        let buf = coordinates(2, 2);
+        let bordered = add_border(&buf, 1, BorderMode::Constant(Color::BLUE)).unwrap();
+
+        assert_eq!((4, 4), bordered.dimensions());
+        assert_eq!(12, pixels_of(&bordered, Color::BLUE).len());
+        assert_eq!(coordinate(0, 0), bordered.get_pixel(1, 1).unwrap());
+        assert_eq!(coordinate(1, 1), bordered.get_pixel(2, 2).unwrap());
+    }
+
+    #[test]
+    fn add_border_replicate() {
+        let buf = coordinates(2, 2);
+        let bordered = add_border(&buf, 2, BorderMode::Replicate).unwrap();
+
+        assert_eq!((6, 6), bordered.dimensions());
+        assert_eq!(coordinate(0, 0), bordered.get_pixel(0, 0).unwrap());
+        assert_eq!(coordinate(1, 0), bordered.get_pixel(5, 1).unwrap());
+        assert_eq!(coordinate(0, 1), bordered.get_pixel(1, 4).unwrap());
+        assert_eq!(coordinate(1, 1), bordered.get_pixel(3, 3).unwrap());
+    }
+
+    #[test]
+    fn add_border_wrap() {
+        let buf = coordinates(2, 3);
+        let bordered = add_border(&buf, 1, BorderMode::Wrap).unwrap();
+
+        assert_eq!((4, 5), bordered.dimensions());
+        assert_eq!(coordinate(1, 2), bordered.get_pixel(0, 0).unwrap());
+        assert_eq!(coordinate(0, 0), bordered.get_pixel(3, 4).unwrap());
+        assert_eq!(coordinate(1, 1), bordered.get_pixel(0, 2).unwrap());
+        assert_eq!(coordinate(0, 1), bordered.get_pixel(1, 2).unwrap());
+    }
+
+    #[test]
+    fn add_border_errors() {
+        let buf = coordinates(2, 2);
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            add_border(&buf, u64::MAX / 2, BorderMode::Replicate)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            add_border(&buf, u64::MAX / 4, BorderMode::Wrap)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            add_border(&broken(2, 2), 1, BorderMode::Replicate)
+        );
+    }
+
+    #[test]
+    fn downsample_by_two() {
+        let buf = coordinates(4, 4);
+        let downsampled = downsample(&buf, 2).unwrap();
+
+        assert_eq!((2, 2), downsampled.dimensions());
+        assert_eq!(
+            Color {
+                r: 0.5f32,
+                g: 0.5f32,
+                b: 0f32,
+                a: 1f32,
+            },
+            downsampled.get_pixel(0, 0).unwrap()
+        );
+        assert_eq!(
+            Color {
+                r: 2.5f32,
+                g: 0.5f32,
+                b: 0f32,
+                a: 1f32,
+            },
+            downsampled.get_pixel(1, 0).unwrap()
+        );
+        assert_eq!(
+            Color {
+                r: 2.5f32,
+                g: 2.5f32,
+                b: 0f32,
+                a: 1f32,
+            },
+            downsampled.get_pixel(1, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn downsample_invalid() {
+        let buf = coordinates(4, 3);
+
+        assert_eq!(Err(ColorBufError::InvalidDimensions), downsample(&buf, 0));
+        assert_eq!(Err(ColorBufError::InvalidDimensions), downsample(&buf, 2));
+    }
+
+    #[test]
+    fn mipmaps() {
+        let buf = VecColorBuf::filled(8, 8, Color::RED);
+        let levels = generate_mipmaps(&buf).unwrap();
+
+        assert_eq!(4, levels.len());
+        assert_eq!(buf, levels[0]);
+        assert_eq!((4, 4), levels[1].dimensions());
+        assert_eq!((2, 2), levels[2].dimensions());
+        assert_eq!(VecColorBuf::filled(1, 1, Color::RED), levels[3]);
+    }
+
+    #[test]
+    fn mipmaps_uneven() {
+        let buf = coordinates(5, 2);
+        let dimensions: Vec<(u64, u64)> = generate_mipmaps(&buf)
+            .unwrap()
+            .iter()
+            .map(|level| level.dimensions())
+            .collect();
+
+        assert_eq!(vec![(5, 2), (2, 1), (1, 1)], dimensions);
+
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            generate_mipmaps(&broken(5, 2))
+        );
+    }
+
+    #[test]
+    fn composite_premultiplied_two_layers() {
+        let mut dst = VecColorBuf::filled(
+            2,
+            2,
+            Color {
+                r: 1f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0.5f32,
+            },
+        );
+        let src = VecColorBuf::filled(
+            1,
+            1,
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 1f32,
+                a: 0.5f32,
+            },
+        );
+        composite_premultiplied(&mut dst, &src, 1, 1).unwrap();
+
+        // Premultiplied, the layers are (0.5, 0, 0, 0.5) and (0, 0, 0.5, 0.5), which composite to
+        // (0.25, 0, 0.5, 0.75).
+        assert!(dst.get_pixel(1, 1).unwrap().approx_eq(
+            &Color {
+                r: 1f32 / 3f32,
+                g: 0f32,
+                b: 2f32 / 3f32,
+                a: 0.75f32,
+            },
+            1e-6f32
+        ));
+        assert_eq!(
+            Color {
+                r: 1f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0.5f32,
+            },
+            dst.get_pixel(0, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn composite_premultiplied_transparent_dst() {
+        let mut dst = VecColorBuf::filled(
+            1,
+            1,
+            Color {
+                r: 1f32,
+                g: 1f32,
+                b: 0f32,
+                a: 0f32,
+            },
+        );
+        let src = VecColorBuf::filled(
+            1,
+            1,
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 1f32,
+                a: 0.5f32,
+            },
+        );
+        composite_premultiplied(&mut dst, &src, 0, 0).unwrap();
+
+        assert!(dst.get_pixel(0, 0).unwrap().approx_eq(
+            &Color {
+                r: 0f32,
+                g: 0f32,
+                b: 1f32,
+                a: 0.5f32,
+            },
+            1e-6f32
+        ));
+    }
+
+    #[test]
+    fn blit_masked_half() {
+        let mut dst = VecColorBuf::filled(4, 2, Color::BLUE);
+        let src = VecColorBuf::filled(4, 2, Color::RED);
+        let mut mask = VecColorBuf::filled(4, 2, Color::TRANSPARENT);
+        fill_rect(&mut mask, 0, 0, 2, 2, &Color::WHITE).unwrap();
+
+        blit_masked(&mut dst, &src, &mask, 0, 0).unwrap();
+
+        assert_eq!(
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)],
+            pixels_of(&dst, Color::RED)
+        );
+        assert_eq!(
+            vec![(2, 0), (3, 0), (2, 1), (3, 1)],
+            pixels_of(&dst, Color::BLUE)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            blit_masked(&mut dst, &src, &VecColorBuf::new(2, 2), 0, 0)
+        );
+    }
+
+    #[test]
+    fn blend_mode_multiply_white() {
+        let color = Color {
+            r: 0.2f32,
+            g: 0.4f32,
+            b: 0.6f32,
+            a: 0.8f32,
+        };
+        let mut dst = VecColorBuf::filled(2, 2, color);
+        blend_mode(
+            &mut dst,
+            &VecColorBuf::filled(2, 2, Color::WHITE),
+            BlendMode::Multiply,
+        )
+        .unwrap();
+
+        assert_eq!(VecColorBuf::filled(2, 2, color), dst);
+    }
+
+    #[test]
+    fn blend_mode_add_saturates() {
+        let gray = Color {
+            r: 0.75f32,
+            g: 0.75f32,
+            b: 0.25f32,
+            a: 1f32,
+        };
+        let mut dst = VecColorBuf::filled(1, 1, gray);
+        blend_mode(&mut dst, &VecColorBuf::filled(1, 1, gray), BlendMode::Add).unwrap();
+
+        assert_eq!(
+            Color {
+                r: 1f32,
+                g: 1f32,
+                b: 0.5f32,
+                a: 1f32,
+            },
+            dst.get_pixel(0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn blend_mode_others() {
+        let gray = Color {
+            r: 0.5f32,
+            g: 0.5f32,
+            b: 0.5f32,
+            a: 1f32,
+        };
+        let mut dst = VecColorBuf::filled(1, 1, gray);
+        blend_mode(
+            &mut dst,
+            &VecColorBuf::filled(1, 1, gray),
+            BlendMode::Screen,
+        )
+        .unwrap();
+        assert_eq!(
+            Color {
+                r: 0.75f32,
+                g: 0.75f32,
+                b: 0.75f32,
+                a: 1f32,
+            },
+            dst.get_pixel(0, 0).unwrap()
+        );
+
+        blend_mode(
+            &mut dst,
+            &VecColorBuf::filled(1, 1, Color::WHITE),
+            BlendMode::Difference,
+        )
+        .unwrap();
+        assert_eq!(
+            Color {
+                r: 0.25f32,
+                g: 0.25f32,
+                b: 0.25f32,
+                a: 1f32,
+            },
+            dst.get_pixel(0, 0).unwrap()
+        );
+
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            blend_mode(&mut dst, &VecColorBuf::new(2, 1), BlendMode::Add)
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! [`ColorBuf`]s stored in memory.
+//!
+//! # About
+//!
+//! This module gives a [`ColorBuf`] which stores its pixels directly as [`Color`]s, so that no
+//! encoding or decoding has to happen when the pixels are accessed. This is useful when building a
+//! buffer from scratch and only converting it to a bitmap at the end.
+//!
+//! [`ColorBuf`]: ../trait.ColorBuf.html
+//! [`Color`]: ../struct.Color.html
+
+use crate::{Color, ColorBuf, ColorBufError};
+use std::convert::TryFrom;
+use std::result::Result;
+
+/// [`ColorBuf`] backed by a `Vec` of [`Color`]s.
+///
+/// [`ColorBuf`]: ../trait.ColorBuf.html
+/// [`Color`]: ../struct.Color.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct VecColorBuf {
+    data: Vec<Color>,
+
+    width: u64,
+    height: u64,
+}
+
+impl VecColorBuf {
+    /// Returns a new color buffer of the given size filled with transparent black.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the amount of pixels doesn't fit into a `usize`.
+    pub fn new(width: u64, height: u64) -> VecColorBuf {
+        VecColorBuf::filled(width, height, Color::TRANSPARENT)
+    }
+
+    /// Returns a new color buffer of the given size filled with `color`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the amount of pixels doesn't fit into a `usize`.
+    pub fn filled(width: u64, height: u64, color: Color) -> VecColorBuf {
+        let len = width
+            .checked_mul(height)
+            .and_then(|len| usize::try_from(len).ok())
+            .expect("pixel count overflows usize");
+        VecColorBuf {
+            data: vec![color; len],
+            width,
+            height,
+        }
+    }
+
+    /// Iterates over the rows of this buffer from top to bottom.
+    ///
+    /// Every item holds the colors of one row from left to right.
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        let width = self.width as usize;
+        (0..self.height as usize).map(move |y| &self.data[y * width..(y + 1) * width])
+    }
+
+    /// Returns a parallel iterator over the rows of this buffer for modification.
+    ///
+    /// Every item holds the colors of one row from left to right, and the rows don't overlap, so
+    /// they can be modified from different threads. Only available with the `rayon` feature.
+    #[cfg(feature = "rayon")]
+    pub fn par_rows_mut(&mut self) -> rayon::slice::ChunksMut<'_, Color> {
+        use rayon::prelude::*;
+
+        // `par_chunks_mut` panics on a zero chunk size, and a buffer without columns has no pixels
+        // to modify anyway.
+        let width = (self.width as usize).max(1);
+        self.data.par_chunks_mut(width)
+    }
+
+    fn get_offset(&self, x: u64, y: u64) -> usize {
+        (y * self.width + x) as usize
+    }
+}
+
+impl ColorBuf for VecColorBuf {
+    fn get_pixel(&self, x: u64, y: u64) -> Result<Color, ColorBufError> {
+        if x >= self.width || y >= self.height {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        Ok(self.data[self.get_offset(x, y)])
+    }
+
+    unsafe fn get_pixel_unchecked(&self, x: u64, y: u64) -> Color {
+        *self.data.get_unchecked(self.get_offset(x, y))
+    }
+
+    fn set_pixel(&mut self, x: u64, y: u64, color: &Color) -> Result<(), ColorBufError> {
+        if x >= self.width || y >= self.height {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        let index = self.get_offset(x, y);
+        self.data[index] = *color;
+        Ok(())
+    }
+
+    unsafe fn set_pixel_unchecked(&mut self, x: u64, y: u64, color: &Color) {
+        let index = self.get_offset(x, y);
+        *self.data.get_unchecked_mut(index) = *color;
+    }
+
+    fn get_width(&self) -> u64 {
+        self.width
+    }
+
+    fn get_height(&self) -> u64 {
+        self.height
+    }
+
+    fn copy_from<S: ColorBuf>(&mut self, src: &S) -> Result<(), ColorBufError> {
+        if src.get_width() != self.width || src.get_height() != self.height {
+            return Err(ColorBufError::InvalidDimensions);
+        }
+        // Copy whole rows straight into the `Vec`, so sources that decode rows quickly can.
+        for y in 0..self.height {
+            let start = self.get_offset(0, y);
+            self.data[start..start + self.width as usize].copy_from_slice(&src.get_row(y)?);
+        }
+        Ok(())
+    }
+
+    fn to_color_vec(&self) -> Vec<Color> {
+        // The pixels are already stored row by row.
+        self.data.clone()
+    }
+
+    fn get_row(&self, y: u64) -> Result<Vec<Color>, ColorBufError> {
+        if y >= self.height {
+            return Err(ColorBufError::InvalidCoordinate);
+        }
+        let start = self.get_offset(0, y);
+        Ok(self.data[start..start + self.width as usize].to_vec())
+    }
+
+    fn fill(&mut self, color: &Color) -> Result<(), ColorBufError> {
+        for pixel in self.data.iter_mut() {
+            *pixel = *color;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl crate::ParRowsMut for VecColorBuf {
+    fn par_rows_mut(&mut self) -> impl rayon::iter::IndexedParallelIterator<Item = &mut [Color]> {
+        VecColorBuf::par_rows_mut(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_is_transparent_black() {
+        let colorbuf = VecColorBuf::new(2, 3);
+
+        assert_eq!(2, colorbuf.get_width());
+        assert_eq!(3, colorbuf.get_height());
+        assert_eq!(
+            Color {
+                r: 0f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0f32,
+            },
+            colorbuf.get_pixel(1, 2).unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel count overflows usize")]
+    fn overflowing_size() {
+        VecColorBuf::new(u64::MAX, 2);
+    }
+
+    #[test]
+    fn modification() {
+        let white = Color {
+            r: 1f32,
+            g: 1f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let blue = Color {
+            r: 0f32,
+            g: 0f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let mut colorbuf = VecColorBuf::filled(2, 2, white);
+        colorbuf.set_pixel(1, 0, &blue).unwrap();
+
+        assert_eq!(white, colorbuf.get_pixel(0, 0).unwrap());
+        assert_eq!(blue, colorbuf.get_pixel(1, 0).unwrap());
+        assert_eq!(white, colorbuf.get_pixel(0, 1).unwrap());
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut colorbuf = VecColorBuf::new(2, 2);
+
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            colorbuf.get_pixel(2, 0)
+        );
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            colorbuf.set_pixel(
+                0,
+                2,
+                &Color {
+                    r: 0f32,
+                    g: 0f32,
+                    b: 0f32,
+                    a: 0f32,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn fill() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let mut colorbuf = VecColorBuf::new(3, 3);
+        colorbuf.fill(&red).unwrap();
+
+        assert_eq!(red, colorbuf.get_pixel(0, 0).unwrap());
+        assert_eq!(red, colorbuf.get_pixel(1, 1).unwrap());
+        assert_eq!(red, colorbuf.get_pixel(2, 2).unwrap());
+    }
+
+    #[test]
+    fn unchecked_access() {
+        let blue = Color {
+            r: 0f32,
+            g: 0f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let mut colorbuf = VecColorBuf::new(2, 2);
+
+        unsafe {
+            colorbuf.set_pixel_unchecked(1, 1, &blue);
+            assert_eq!(blue, colorbuf.get_pixel_unchecked(1, 1));
+        }
+        assert_eq!(blue, colorbuf.get_pixel(1, 1).unwrap());
+    }
+
+    #[test]
+    fn copy_from() {
+        let mut src = VecColorBuf::new(2, 2);
+        src.set_pixel(
+            1,
+            0,
+            &Color {
+                r: 1f32,
+                g: 0f32,
+                b: 0f32,
+                a: 1f32,
+            },
+        )
+        .unwrap();
+        let mut dst = VecColorBuf::filled(
+            2,
+            2,
+            Color {
+                r: 1f32,
+                g: 1f32,
+                b: 1f32,
+                a: 1f32,
+            },
+        );
+        dst.copy_from(&src).unwrap();
+
+        assert_eq!(src, dst);
+    }
+
+    #[test]
+    fn copy_from_mismatched() {
+        let src = VecColorBuf::new(2, 3);
+        let mut dst = VecColorBuf::new(3, 2);
+
+        assert_eq!(Err(ColorBufError::InvalidDimensions), dst.copy_from(&src));
+    }
+
+    #[test]
+    fn in_bounds() {
+        let colorbuf = VecColorBuf::new(3, 2);
+
+        assert!(colorbuf.in_bounds(0, 0));
+        assert!(colorbuf.in_bounds(2, 1));
+        assert!(!colorbuf.in_bounds(3, 1));
+        assert!(!colorbuf.in_bounds(2, 2));
+    }
+
+    #[test]
+    fn to_color_vec() {
+        let mut colorbuf = VecColorBuf::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                colorbuf
+                    .set_pixel(
+                        x,
+                        y,
+                        &Color {
+                            r: x as f32 / 2f32,
+                            g: y as f32,
+                            b: 0f32,
+                            a: 1f32,
+                        },
+                    )
+                    .unwrap();
+            }
+        }
+        let colors = colorbuf.to_color_vec();
+
+        assert_eq!(6, colors.len());
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(
+                    colorbuf.get_pixel(x, y).unwrap(),
+                    colors[(y * 3 + x) as usize]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sample_uv() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let blue = Color {
+            r: 0f32,
+            g: 0f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let mut colorbuf = VecColorBuf::new(4, 3);
+        colorbuf.set_pixel(0, 0, &red).unwrap();
+        colorbuf.set_pixel(3, 2, &blue).unwrap();
+
+        assert_eq!(red, colorbuf.sample_uv(0f32, 0f32).unwrap());
+        assert_eq!(blue, colorbuf.sample_uv(0.999f32, 0.999f32).unwrap());
+        assert_eq!(blue, colorbuf.sample_uv(1f32, 1f32).unwrap());
+        assert_eq!(red, colorbuf.sample_uv(-1f32, -1f32).unwrap());
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            VecColorBuf::new(0, 0).sample_uv(0.5f32, 0.5f32)
+        );
+    }
+
+    #[test]
+    fn sample_uv_bilinear() {
+        let black = Color {
+            r: 0f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let white = Color {
+            r: 1f32,
+            g: 1f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let mut colorbuf = VecColorBuf::filled(2, 1, black);
+        colorbuf.set_pixel(1, 0, &white).unwrap();
+
+        assert_eq!(
+            Color {
+                r: 0.5f32,
+                g: 0.5f32,
+                b: 0.5f32,
+                a: 1f32,
+            },
+            colorbuf.sample_uv_bilinear(0.5f32, 0.5f32).unwrap()
+        );
+        assert_eq!(black, colorbuf.sample_uv_bilinear(0f32, 0f32).unwrap());
+        assert_eq!(white, colorbuf.sample_uv_bilinear(1f32, 1f32).unwrap());
+        assert_eq!(
+            Err(ColorBufError::InvalidCoordinate),
+            VecColorBuf::new(0, 1).sample_uv_bilinear(0.5f32, 0.5f32)
+        );
+    }
+
+    #[test]
+    fn rows() {
+        let red = Color {
+            r: 1f32,
+            g: 0f32,
+            b: 0f32,
+            a: 1f32,
+        };
+        let mut colorbuf = VecColorBuf::new(2, 2);
+        colorbuf.set_pixel(1, 0, &red).unwrap();
+        let rows: Vec<&[Color]> = colorbuf.rows().collect();
+
+        assert_eq!(2, rows.len());
+        assert_eq!(&[Color::TRANSPARENT, red], rows[0]);
+        assert_eq!(&[Color::TRANSPARENT, Color::TRANSPARENT], rows[1]);
+        assert_eq!(3, VecColorBuf::new(0, 3).rows().count());
+    }
+
+    #[test]
+    fn fill_rect() {
+        let blue = Color {
+            r: 0f32,
+            g: 0f32,
+            b: 1f32,
+            a: 1f32,
+        };
+        let mut colorbuf = VecColorBuf::new(4, 3);
+        colorbuf.fill_rect(1, 1, 3, 2, &blue).unwrap();
+
+        assert_eq!(blue, colorbuf.get_pixel(1, 1).unwrap());
+        assert_eq!(blue, colorbuf.get_pixel(3, 2).unwrap());
+        assert_ne!(blue, colorbuf.get_pixel(0, 1).unwrap());
+        assert_ne!(blue, colorbuf.get_pixel(1, 0).unwrap());
+        assert_eq!(
+            Err(ColorBufError::InvalidDimensions),
+            colorbuf.fill_rect(2, 0, 3, 1, &blue)
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_rows_mut() {
+        use rayon::prelude::*;
+
+        let mut colorbuf = VecColorBuf::new(3, 4);
+        colorbuf.par_rows_mut().enumerate().for_each(|(y, row)| {
+            assert_eq!(3, row.len());
+            for color in row.iter_mut() {
+                color.g = y as f32;
+            }
+        });
+
+        for y in 0..4 {
+            assert_eq!(y as f32, colorbuf.get_pixel(2, y).unwrap().g);
+        }
+    }
+}